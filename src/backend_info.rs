@@ -0,0 +1,38 @@
+use gleam::gl;
+use gleam::gl::types::GLenum;
+use std::ffi::CStr;
+
+use BackendKind;
+
+/// Everything needed to identify the driver that produced an issue,
+/// meant for bug reports and diagnostic dumps.
+#[derive(Clone, Debug)]
+pub struct BackendInfo {
+    pub kind: BackendKind,
+    pub version: String,
+    pub renderer: String,
+    pub vendor: String,
+}
+
+fn get_string(name: GLenum) -> String {
+    unsafe {
+        let ptr = gl::GetString(name);
+        if ptr.is_null() {
+            return String::new();
+        }
+
+        String::from_utf8_lossy(CStr::from_ptr(ptr as *const _).to_bytes()).into_owned()
+    }
+}
+
+impl BackendInfo {
+    /// Queries the GL strings of the current context.
+    pub fn detect(kind: BackendKind) -> BackendInfo {
+        BackendInfo {
+            kind: kind,
+            version: get_string(gl::VERSION),
+            renderer: get_string(gl::RENDERER),
+            vendor: get_string(gl::VENDOR),
+        }
+    }
+}
@@ -1,9 +1,177 @@
 use geom::Size2D;
 use gleam::gl;
-use gleam::gl::types::{GLuint, GLenum};
+use gleam::gl::types::{GLuint, GLenum, GLint, GLsizei, GLfloat, GLbitfield, GLboolean};
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::mem;
+use std::ptr;
+use std::rc::Rc;
 
 use GLContext;
 use GLContextAttributes;
+use TextureParameters;
+use GLContextCapabilities;
+use EXT_PACKED_DEPTH_STENCIL;
+use GLFeature;
+use GLStateGuard;
+use PersistentReadback;
+use MappedReadback;
+use Snapshot;
+use CapturedFrame;
+use EXT_BGRA_READ;
+use EXT_MULTIVIEW;
+use EXT_BGRA8_FORMAT;
+use Fence;
+use mapped_readback;
+use ToGLSize;
+use GLContextError;
+use Result;
+use pixels;
+use gl_errors::{check_gl_error, describe_framebuffer_status, get_error};
+use gl_feature::get_gl_version;
+use gl_validation::validate_request;
+use gl_context::{current_context_id, deletion_queue_of};
+use pending_deletions::PendingDeletions;
+use quad_blit;
+
+// NOTE: These aren't exposed by the GLES bindings, so we define
+// them here and probe for support at runtime.
+const RGBA8: GLenum = 0x8058;
+const STENCIL_INDEX4: GLenum = 0x8D47;
+const STENCIL_INDEX16: GLenum = 0x8D49;
+const DEPTH24_STENCIL8: GLenum = 0x88F0;
+const DEPTH32F_STENCIL8: GLenum = 0x8CAD;
+const DEPTH_STENCIL_ATTACHMENT: GLenum = 0x821A;
+const PACK_ROW_LENGTH: GLenum = 0x0D02;
+const READ_FRAMEBUFFER: GLenum = 0x8CA8;
+const TEXTURE_WIDTH: GLenum = 0x1000;
+const TEXTURE_HEIGHT: GLenum = 0x1001;
+const DRAW_FRAMEBUFFER: GLenum = 0x8CA9;
+const R8I: GLenum = 0x8231;
+const R8UI: GLenum = 0x8232;
+const R32I: GLenum = 0x8235;
+const R32UI: GLenum = 0x8236;
+const RGBA32UI: GLenum = 0x8D70;
+const RGBA16UI: GLenum = 0x8D76;
+const RGBA8UI: GLenum = 0x8D7C;
+const RGBA32I: GLenum = 0x8D82;
+const RGBA16I: GLenum = 0x8D88;
+const RGBA8I: GLenum = 0x8D8E;
+const R16F: GLenum = 0x822D;
+const R32F: GLenum = 0x822E;
+const RG16F: GLenum = 0x822F;
+const RG32F: GLenum = 0x8230;
+const RGBA32F: GLenum = 0x8814;
+const RGB32F: GLenum = 0x8815;
+const RGBA16F: GLenum = 0x881A;
+const RGB16F: GLenum = 0x881B;
+const R11F_G11F_B10F: GLenum = 0x8C3A;
+const CLAMP_READ_COLOR: GLenum = 0x891C;
+const RGBA_INTEGER: GLenum = 0x8D99;
+const FRAMEBUFFER_DEFAULT_WIDTH: GLenum = 0x9310;
+const FRAMEBUFFER_DEFAULT_HEIGHT: GLenum = 0x9311;
+const FRAMEBUFFER_DEFAULT_SAMPLES: GLenum = 0x9313;
+const BGRA: GLenum = 0x80E1;
+const RED: GLenum = 0x1903;
+const RG: GLenum = 0x8227;
+const RGB_INTEGER: GLenum = 0x8D98;
+const HALF_FLOAT: GLenum = 0x140B;
+const HALF_FLOAT_OES: GLenum = 0x8D61;
+const UNSIGNED_INT_2_10_10_10_REV: GLenum = 0x8368;
+const IMPLEMENTATION_COLOR_READ_TYPE: GLenum = 0x8B9A;
+const IMPLEMENTATION_COLOR_READ_FORMAT: GLenum = 0x8B9B;
+const RGBA8_SNORM: GLenum = 0x8F97;
+const READ_ONLY: GLenum = 0x88B8;
+const WRITE_ONLY: GLenum = 0x88B9;
+const READ_WRITE: GLenum = 0x88BA;
+const MAX_IMAGE_UNITS: GLenum = 0x8F38;
+const MAX_VIEWS_OVR: GLenum = 0x9631;
+const DEPTH_COMPONENT24: GLenum = 0x81A6;
+const COLOR: GLenum = 0x1800;
+const RENDERBUFFER_SAMPLES: GLenum = 0x8CAB;
+const BGRA8_EXT: GLenum = 0x93A1;
+const RENDERBUFFER_INTERNAL_FORMAT: GLenum = 0x8D44;
+const FRAMEBUFFER_INCOMPLETE_MULTISAMPLE: GLenum = 0x8D56;
+const BUFFER_SIZE: GLenum = 0x8764;
+const DEPTH: GLenum = 0x1801;
+const STENCIL: GLenum = 0x1802;
+const FRAMEBUFFER_ATTACHMENT_DEPTH_SIZE: GLenum = 0x8216;
+const FRAMEBUFFER_ATTACHMENT_STENCIL_SIZE: GLenum = 0x8217;
+
+/// How the compositing helpers (like `blit_to_default`) combine the
+/// draw buffer contents with the destination.
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum CompositeOp {
+    /// Replace the destination, without blending
+    Copy,
+    /// Premultiplied source-over: `src + dst * (1 - src_alpha)`
+    SourceOver,
+}
+
+// How the storage of a color texture we created was allocated
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TextureStorage {
+    // With glTexImage2D
+    Mutable,
+    // With glTexStorage2D, so it can't be reallocated, only replaced
+    Immutable,
+}
+
+/// The GL object holding the rendered colors of a draw buffer, for
+/// APIs that import GL objects directly, like hardware video encoders.
+/// See `DrawBuffer::export_color_buffer`.
+///
+/// Per platform, the object is usually registered through:
+///
+///  * CUDA (for NVENC, on Linux and Windows): `cuGraphicsGLRegisterImage`
+///    with `target` and `name`
+///  * VAAPI (Linux): an EGLImage created from the texture
+///    (`EGL_GL_TEXTURE_2D_KHR`), exported as a dmabuf
+///  * Media Foundation (Windows): `WGL_NV_DX_interop2`, with a shared
+///    D3D texture
+///  * VideoToolbox (OS X): it needs IOSurface-backed textures, which
+///    the draw buffer doesn't create, so the contents must be copied
+///    into one
+pub struct ExportedColorBuffer {
+    /// `GL_TEXTURE_2D` or `GL_RENDERBUFFER`
+    pub target: GLenum,
+    pub name: GLuint,
+    pub format: GLenum,
+    pub size: Size2D<i32>,
+    /// Signaled when the rendering issued before the export completes,
+    /// the consumer must wait for it before reading. If sync objects
+    /// aren't supported this is `None`, and the rendering was already
+    /// finished with `glFinish`.
+    ///
+    /// The fence is a GL sync object, so only contexts of the same share
+    /// group can wait for it on the GPU. For consumers in other APIs or
+    /// processes (dmabuf, IOSurface), call `Fence::client_wait` before
+    /// handing the buffer over.
+    pub fence: Option<Fence>,
+}
+
+/// What `DrawBuffer::prepare_for_handoff` returns: the color object,
+/// its format and size, and the fence the consumer must wait for.
+pub type Handoff = ExportedColorBuffer;
+
+// glFramebufferTextureMultiviewOVR, loaded at runtime
+type FramebufferTextureMultiviewFn = extern "system" fn(GLenum, GLenum, GLuint, GLint, GLint, GLsizei);
+
+// A multiview (GL_OVR_multiview2) color attachment, see
+// `DrawBuffer::set_color_texture_multiview`
+#[derive(Clone, Copy)]
+struct Multiview {
+    base_view: GLint,
+    num_views: GLsizei,
+    attach: FramebufferTextureMultiviewFn,
+}
+
+// Everything `DrawBuffer::new_lazy` needs to allocate later
+#[derive(Clone, Copy)]
+struct LazyAllocation {
+    capabilities: GLContextCapabilities,
+    samples: GLsizei,
+}
 
 /// This structure represents an offscreen context
 /// draw buffer. It has a framebuffer, with at least
@@ -12,57 +180,2813 @@ use GLContextAttributes;
 /// requirements.
 pub struct DrawBuffer {
     size: Size2D<i32>,
+    // The size of the depth and stencil buffers, usually the same
+    // as `size`. See `resize_depth_stencil`.
+    depth_stencil_size: Size2D<i32>,
     framebuffer: GLuint,
     stencil_render_buffer: GLuint,
     depth_render_buffer: GLuint,
     color_render_buffer: GLuint,
-    // samples: GLsizei,
+    // Whether we must delete these on drop. Every object (and attachment)
+    // we create is owned, user-supplied ones may not be.
+    framebuffer_owned: bool,
+    stencil_render_buffer_owned: bool,
+    depth_render_buffer_owned: bool,
+    // Whether we must delete the color render buffer on drop.
+    // This is false for user-supplied render buffers.
+    color_render_buffer_owned: bool,
+    // If non-zero, the color attachment is this texture
+    // instead of `color_render_buffer`.
+    color_texture: GLuint,
+    color_texture_owned: bool,
+    // The mip level of `color_texture` we render to
+    color_texture_level: GLint,
+    // None unless we created `color_texture`
+    color_texture_storage: Option<TextureStorage>,
+    color_format: GLenum,
+    // Additional color attachments (COLOR_ATTACHMENT1 onwards) for
+    // multiple render targets. They have the same format as the first.
+    extra_color_render_buffers: Vec<GLuint>,
+    extra_color_render_buffers_owned: bool,
+    // If non-zero, the depth attachment is this texture
+    // instead of `depth_render_buffer`.
+    depth_texture: GLuint,
+    depth_texture_owned: bool,
+    depth_format: GLenum,
+    stencil_format: GLenum,
+    // Whether `read_pixels` returns the rows top to bottom
+    // (GL returns them bottom to top).
+    flip_y: bool,
+    // Antialiasing samples of every render buffer, zero if not multisampled
+    samples: GLsizei,
+    // The attributes used to create the draw buffer
+    attributes: GLContextAttributes,
+    // Whether the contents have been written since the storage was
+    // (re)allocated. See `contents_defined`.
+    contents_defined: Cell<bool>,
+    // How the compositing helpers should blend our contents
+    composite_op: CompositeOp,
+    // If the draw buffer was created with `new_lazy` and not allocated
+    // yet, what to allocate it with, and the values of any clear issued
+    // before the allocation.
+    lazy: Option<LazyAllocation>,
+    pending_clear: Cell<Option<([GLfloat; 4], GLfloat, GLint)>>,
+    // Whether `detach_all` was called and nothing was attached since
+    detached: bool,
+    // Color channels the crate's helpers write, see `set_color_mask`
+    color_mask: [bool; 4],
+    // If set, `color_texture` is a 2D array texture attached as
+    // multiview
+    multiview: Option<Multiview>,
+    // Single-sampled framebuffer (and its render buffer) multisampled
+    // contents are resolved into before reading them, created on first
+    // use and kept until we're resized or dropped
+    resolve_target: Cell<Option<(GLuint, GLuint)>>,
+    // What was chosen for each requested attribute, and why, in
+    // allocation order. See `resolution_report`.
+    resolution_log: Vec<String>,
+    // Whether this wraps the default framebuffer (see
+    // `default_framebuffer`), so there's nothing to (re)allocate
+    is_default_framebuffer: bool,
+    // The id and deletion queue of the context we were created with,
+    // so objects can be deleted later if we're dropped while it isn't
+    // current (see `GLContext::flush_pending_deletions`).
+    owner: Option<(usize, Rc<RefCell<PendingDeletions>>)>,
+}
+
+// Whether textures with this format can be bound as images. This is
+// the GLES 3.1 list, desktop GL supports a few more.
+fn is_image_format(format: GLenum) -> bool {
+    match format {
+        RGBA32F | RGBA16F | R32F | RGBA8 | RGBA8_SNORM |
+        RGBA32I | RGBA16I | RGBA8I | R32I |
+        RGBA32UI | RGBA16UI | RGBA8UI | R32UI => true,
+        _ => false,
+    }
+}
+
+// The size of a pixel returned by glReadPixels with `format` and `ty`,
+// if we know them.
+fn read_pixel_size(format: GLenum, ty: GLenum) -> Option<usize> {
+    let components = match format {
+        gl::RGBA | BGRA | RGBA_INTEGER => 4,
+        gl::RGB | RGB_INTEGER => 3,
+        RG | gl::LUMINANCE_ALPHA => 2,
+        RED | gl::ALPHA | gl::LUMINANCE => 1,
+        _ => return None,
+    };
+
+    match ty {
+        gl::UNSIGNED_BYTE | gl::BYTE => Some(components),
+        gl::UNSIGNED_SHORT | gl::SHORT | HALF_FLOAT | HALF_FLOAT_OES => Some(components * 2),
+        gl::UNSIGNED_INT | gl::INT | gl::FLOAT => Some(components * 4),
+        gl::UNSIGNED_SHORT_5_6_5 | gl::UNSIGNED_SHORT_4_4_4_4 | gl::UNSIGNED_SHORT_5_5_5_1 => Some(2),
+        UNSIGNED_INT_2_10_10_10_REV => Some(4),
+        _ => None,
+    }
+}
+
+/// Helper function to create an RGBA8 (or BGRA8) color texture, with
+/// immutable storage if supported, and the given sampling parameters.
+/// Returns the texture and how its storage was allocated.
+fn create_color_texture(size: &Size2D<i32>, format: GLenum, parameters: &TextureParameters)
+    -> (GLuint, TextureStorage) {
+    let mut texture = 0;
+    // NOTE: glTexStorage2D only accepts BGRA8 with GL_EXT_texture_storage
+    let storage = if format != BGRA8_EXT && GLFeature::is_supported(GLFeature::TextureStorage) {
+        TextureStorage::Immutable
+    } else {
+        TextureStorage::Mutable
+    };
+
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+        debug_assert!(texture != 0);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        set_texture_parameters(parameters);
+        allocate_color_texture(storage, format, size);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+
+    (texture, storage)
+}
+
+// NOTE: Assumes the texture is bound
+fn set_texture_parameters(parameters: &TextureParameters) {
+    unsafe {
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, parameters.min_filter as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, parameters.mag_filter as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, parameters.wrap_s as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, parameters.wrap_t as GLint);
+    }
+}
+
+// NOTE: Assumes the texture is bound. Immutable storage can only be
+// allocated once.
+fn allocate_color_texture(storage: TextureStorage, format: GLenum, size: &Size2D<i32>) {
+    let size = storage_size(size);
+
+    // NOTE: GLES2 requires an unsized internal format
+    let unsized_format = if format == BGRA8_EXT { BGRA } else { gl::RGBA };
+
+    unsafe {
+        match storage {
+            TextureStorage::Immutable => {
+                gl::TexStorage2D(gl::TEXTURE_2D, 1, format, size.width, size.height);
+            }
+            TextureStorage::Mutable => {
+                gl::TexImage2D(gl::TEXTURE_2D, 0, unsized_format as GLint,
+                               size.width, size.height, 0,
+                               unsized_format, gl::UNSIGNED_BYTE, ptr::null());
+            }
+        }
+    }
+}
+
+/// The size of the storage we allocate for a buffer of `size`. Zero-size
+/// draw buffers are legal (as in WebGL), but zero-size storage isn't
+/// framebuffer complete, so those get 1x1 storage instead, which is
+/// never read.
+fn storage_size(size: &Size2D<i32>) -> Size2D<i32> {
+    Size2D(cmp::max(size.width, 1), cmp::max(size.height, 1))
+}
+
+/// Helper function to create a render buffer, multisampled
+/// if `samples` is non-zero
+fn create_render_buffer(format: GLenum, size: &Size2D<i32>, samples: GLsizei) -> GLuint {
+    let mut ret: GLuint = 0;
+
+    unsafe {
+        gl::GenRenderbuffers(1, &mut ret);
+    }
+
+    resize_render_buffer(ret, format, size, samples);
+
+    ret
+}
+
+/// Reallocates the storage of an existing render buffer.
+fn resize_render_buffer(render_buffer: GLuint, format: GLenum, size: &Size2D<i32>, samples: GLsizei) {
+    let size = storage_size(size);
+
+    unsafe {
+        gl::BindRenderbuffer(gl::RENDERBUFFER, render_buffer);
+        if samples > 0 {
+            gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, format,
+                                               size.width, size.height);
+        } else {
+            gl::RenderbufferStorage(gl::RENDERBUFFER, format, size.width, size.height);
+        }
+    }
+}
+
+// A readable name of the formats we allocate, for diagnostics
+fn format_name(format: GLenum) -> String {
+    match format {
+        RGBA8 => "RGBA8".to_string(),
+        BGRA8_EXT => "BGRA8".to_string(),
+        gl::RGBA4 => "RGBA4".to_string(),
+        DEPTH24_STENCIL8 => "DEPTH24_STENCIL8".to_string(),
+        gl::DEPTH_COMPONENT16 => "DEPTH_COMPONENT16".to_string(),
+        STENCIL_INDEX4 => "STENCIL_INDEX4".to_string(),
+        gl::STENCIL_INDEX8 => "STENCIL_INDEX8".to_string(),
+        STENCIL_INDEX16 => "STENCIL_INDEX16".to_string(),
+        _ => format!("{:#x}", format),
+    }
+}
+
+/// The amount of samples the implementation actually allocated for
+/// `render_buffer`, which may be more than requested.
+fn render_buffer_samples(render_buffer: GLuint) -> GLsizei {
+    let mut samples: GLint = 0;
+
+    unsafe {
+        gl::BindRenderbuffer(gl::RENDERBUFFER, render_buffer);
+        gl::GetRenderbufferParameteriv(gl::RENDERBUFFER, RENDERBUFFER_SAMPLES, &mut samples);
+    }
+
+    samples
+}
+
+/// Whether every attachment got the `samples` of the color buffer,
+/// as framebuffer completeness requires.
+fn sample_counts_match(samples: GLsizei, attachment_samples: &[GLsizei]) -> bool {
+    attachment_samples.iter().all(|&count| count == samples)
+}
+
+/// Like `create_render_buffer`, but returns `None` if the
+/// implementation rejected the format, and an error if it ran out
+/// of memory, since trying other formats won't help then.
+fn try_create_render_buffer(format: GLenum, size: &Size2D<i32>, samples: GLsizei) -> Result<Option<GLuint>> {
+    unsafe {
+        // Discard previous errors so we only check ours
+        while get_error() != gl::NO_ERROR {}
+    }
+
+    let ret = create_render_buffer(format, size, samples);
+
+    unsafe {
+        match get_error() {
+            gl::NO_ERROR => {},
+            error => {
+                gl::DeleteRenderbuffers(1, &ret);
+
+                if error == gl::OUT_OF_MEMORY {
+                    return Err(GLContextError::OutOfMemory("glRenderbufferStorage"));
+                }
+
+                return Ok(None);
+            }
+        }
+    }
+
+    Ok(Some(ret))
+}
+
+/// Returns the stencil formats to try, in order of preference,
+/// for the requested amount of stencil bits.
+/// `STENCIL_INDEX8` is always the last resort, since it's the only
+/// one every implementation must support.
+fn stencil_formats_for(bits: u8) -> &'static [GLenum] {
+    static FORMATS_16: [GLenum; 2] = [STENCIL_INDEX16, gl::STENCIL_INDEX8];
+    static FORMATS_8: [GLenum; 1] = [gl::STENCIL_INDEX8];
+    static FORMATS_4: [GLenum; 2] = [STENCIL_INDEX4, gl::STENCIL_INDEX8];
+
+    if bits > 8 {
+        &FORMATS_16
+    } else if bits > 4 {
+        &FORMATS_8
+    } else {
+        &FORMATS_4
+    }
+}
+
+/// Whether `format` is a packed depth-stencil format, which
+/// must be attached to `GL_DEPTH_STENCIL_ATTACHMENT`.
+fn is_packed_depth_stencil_format(format: GLenum) -> bool {
+    format == DEPTH24_STENCIL8 || format == DEPTH32F_STENCIL8
+}
+
+/// Whether `format` is an integer (non-normalized) color format.
+/// Blits between integer and non-integer formats are not allowed.
+fn is_integer_format(format: GLenum) -> bool {
+    match format {
+        R8I | R8UI | R32I | R32UI |
+        RGBA8I | RGBA8UI | RGBA16I | RGBA16UI | RGBA32I | RGBA32UI => true,
+        _ => false,
+    }
+}
+
+fn is_unsigned_integer_format(format: GLenum) -> bool {
+    match format {
+        R8UI | R32UI | RGBA8UI | RGBA16UI | RGBA32UI => true,
+        _ => false,
+    }
+}
+
+fn is_float_format(format: GLenum) -> bool {
+    match format {
+        R16F | R32F | RG16F | RG32F | RGB16F | RGB32F |
+        RGBA16F | RGBA32F | R11F_G11F_B10F => true,
+        _ => false,
+    }
 }
 
-/// Helper function to create a render buffer
-/// TODO(ecoal95): We'll need to switch between `glRenderbufferStorage` and
-///   `glRenderbufferStorageMultisample` when we support antialising
-fn create_render_buffer(format: GLenum, size: &Size2D<i32>) -> GLuint {
-    let mut ret: GLuint = 0;
+// Creates a framebuffer with a single-sampled RGBA8 (or RGBA4 on GLES2
+// without GL_OES_rgb8_rgba8) render buffer, for intermediate blits.
+// Returns the framebuffer and the render buffer.
+fn create_blit_target(size: &Size2D<i32>) -> Result<(GLuint, GLuint)> {
+    create_blit_target_with_format(size, RGBA8)
+}
+
+// Like `create_blit_target`, with a render buffer of `format`. Only
+// RGBA8 falls back to RGBA4.
+fn create_blit_target_with_format(size: &Size2D<i32>, format: GLenum)
+    -> Result<(GLuint, GLuint)> {
+    let render_buffer = match try!(try_create_render_buffer(format, size, 0)) {
+        Some(render_buffer) => render_buffer,
+        None if format == RGBA8 => match try!(try_create_render_buffer(gl::RGBA4, size, 0)) {
+            Some(render_buffer) => render_buffer,
+            None => return Err(GLContextError::GLError("Couldn't create the intermediate render buffer")),
+        },
+        None => return Err(GLContextError::GLError("Couldn't create the intermediate render buffer")),
+    };
+
+    let mut framebuffer: GLuint = 0;
+
+    let status = unsafe {
+        gl::GenFramebuffers(1, &mut framebuffer);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0,
+                                    gl::RENDERBUFFER, render_buffer);
+        gl::CheckFramebufferStatus(gl::FRAMEBUFFER)
+    };
+
+    if status != gl::FRAMEBUFFER_COMPLETE {
+        delete_blit_target(framebuffer, render_buffer);
+        return Err(GLContextError::IncompleteFramebuffer(status));
+    }
+
+    Ok((framebuffer, render_buffer))
+}
+
+fn delete_blit_target(framebuffer: GLuint, render_buffer: GLuint) {
+    unsafe {
+        gl::DeleteFramebuffers(1, &framebuffer);
+        gl::DeleteRenderbuffers(1, &render_buffer);
+    }
+}
+
+// Copies the color buffer of `source` into `dest` with glBlitFramebuffer,
+// or drawing a quad if it's not available (GLES2) or we need to blend.
+//
+// Multisampled sources can only be resolved by glBlitFramebuffer,
+// and not scaled at the same time.
+fn copy_color(source: GLuint, source_size: &Size2D<i32>, source_samples: GLsizei,
+              dest: GLuint, dest_size: &Size2D<i32>,
+              filter: GLenum, op: CompositeOp) -> Result<()> {
+    let can_blit = GLFeature::is_supported(GLFeature::FramebufferBlit);
+
+    if source_samples > 0 {
+        if !can_blit {
+            return Err(GLContextError::Unsupported("Multisampled draw buffers can't be resolved without glBlitFramebuffer"));
+        }
+
+        if op != CompositeOp::Copy || source_size != dest_size {
+            return Err(GLContextError::InvalidOperation("Multisampled draw buffers can't be scaled or blended while resolving"));
+        }
+    }
+
+    if !can_blit || op == CompositeOp::SourceOver {
+        return quad_blit::blit(source, source_size, dest, dest_size,
+                               filter, op == CompositeOp::SourceOver);
+    }
+
+    let _guard = GLStateGuard::new();
+
+    unsafe {
+        gl::BindFramebuffer(READ_FRAMEBUFFER, source);
+        gl::BindFramebuffer(DRAW_FRAMEBUFFER, dest);
+        gl::BlitFramebuffer(0, 0, source_size.width, source_size.height,
+                            0, 0, dest_size.width, dest_size.height,
+                            gl::COLOR_BUFFER_BIT, filter);
+    }
+
+    check_gl_error("glBlitFramebuffer")
+}
+
+// NOTE: GLES only has the float version
+#[cfg(any(feature="gles", all(target_os="android", not(feature="gl"))))]
+unsafe fn clear_depth(depth: GLfloat) {
+    gl::ClearDepthf(depth);
+}
+
+#[cfg(not(any(feature="gles", all(target_os="android", not(feature="gl")))))]
+unsafe fn clear_depth(depth: GLfloat) {
+    gl::ClearDepth(depth as f64);
+}
+
+impl DrawBuffer {
+    pub fn new<S: ToGLSize>(context: &GLContext, size: S)
+        -> Result<DrawBuffer> {
+        DrawBuffer::new_with_attributes(context, size, *context.borrow_attributes())
+    }
+
+    /// Like `new`, but configuring the draw buffer with `attrs` instead of
+    /// the context attributes. They're still validated against the context
+    /// capabilities.
+    pub fn new_with_attributes<S: ToGLSize>(context: &GLContext, size: S, attrs: GLContextAttributes)
+        -> Result<DrawBuffer> {
+
+        let size = try!(size.to_gl_size());
+
+        try!(validate_request(&size, &attrs, context.borrow_capabilities()));
+        let samples = DrawBuffer::check_capabilities(&attrs, context.borrow_capabilities());
+
+        try!(context.make_current());
+
+        let mut draw_buffer = try!(DrawBuffer::allocate(size, &attrs, context.borrow_capabilities(), samples));
+        draw_buffer.owner = Some(deletion_queue_of(context));
+
+        Ok(draw_buffer)
+    }
+
+    /// Like `new_with_attributes`, but the GL objects aren't created until
+    /// they're needed: on `bind`, `clear` or any method that reads or
+    /// modifies the attachments. This saves memory for speculative
+    /// render targets that may never be used.
+    ///
+    /// Methods taking `&self` can't allocate, so until `bind` is called
+    /// (or any `&mut self` method), reads fail and clears are deferred.
+    pub fn new_lazy<S: ToGLSize>(context: &GLContext, size: S, attrs: GLContextAttributes)
+        -> Result<DrawBuffer> {
+
+        let size = try!(size.to_gl_size());
+
+        try!(validate_request(&size, &attrs, context.borrow_capabilities()));
+
+        let mut draw_buffer = DrawBuffer::empty(size);
+        draw_buffer.attributes = attrs;
+        draw_buffer.owner = Some(deletion_queue_of(context));
+        draw_buffer.lazy = Some(LazyAllocation {
+            capabilities: *context.borrow_capabilities(),
+            samples: DrawBuffer::check_capabilities(&attrs, context.borrow_capabilities()),
+        });
+
+        Ok(draw_buffer)
+    }
+
+    /// Whether the GL objects have been created. This is always true
+    /// unless the draw buffer was created with `new_lazy`.
+    pub fn is_allocated(&self) -> bool {
+        self.lazy.is_none()
+    }
+
+    /// Allocates the draw buffer if it was created with `new_lazy` and
+    /// wasn't allocated yet, applying any deferred clear.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn ensure_allocated(&mut self) -> Result<()> {
+        let lazy = match self.lazy.take() {
+            Some(lazy) => lazy,
+            None => return Ok(()),
+        };
+
+        self.samples = lazy.samples;
+
+        let attrs = self.attributes;
+        if let Err(err) = self.init(&attrs, &lazy.capabilities) {
+            // Leave it as it was, so a later call can retry
+            self.delete_objects();
+            self.samples = 0;
+            self.lazy = Some(lazy);
+            return Err(err);
+        }
+
+        // NOTE: A deferred clear also marks the contents as defined
+        if let Some((color, depth, stencil)) = self.pending_clear.get() {
+            self.pending_clear.set(None);
+            try!(self.clear_with(color, depth, stencil));
+        }
+
+        Ok(())
+    }
+
+    /// Binds the framebuffer, allocating it first if needed.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn bind(&mut self) -> Result<()> {
+        try!(self.ensure_allocated());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        }
+
+        self.apply_dither();
+
+        if self.attributes.transient {
+            self.invalidate_transient();
+        }
+
+        Ok(())
+    }
+
+    /// Invalidates the depth and stencil buffers if the draw buffer was
+    /// created with the `transient` attribute, telling the driver their
+    /// contents aren't needed anymore. `bind` does it too, so this is
+    /// only needed to end a frame without binding again, like before
+    /// switching to another framebuffer.
+    ///
+    /// NOTE: This leaves the framebuffer bound. Like `drop`, this
+    /// assumes the associated context is current.
+    pub fn invalidate_transient(&self) {
+        if !self.attributes.transient || !self.is_allocated() || self.is_default_framebuffer {
+            return;
+        }
+
+        if !GLFeature::is_supported(GLFeature::InvalidateFramebuffer) {
+            return;
+        }
+
+        let mut attachments = vec![];
+
+        if self.depth_format != 0 {
+            attachments.push(gl::DEPTH_ATTACHMENT);
+        }
+
+        if self.stencil_format != 0 {
+            attachments.push(gl::STENCIL_ATTACHMENT);
+        }
+
+        if attachments.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::InvalidateFramebuffer(gl::FRAMEBUFFER, attachments.len() as GLsizei,
+                                      attachments.as_ptr());
+        }
+    }
+
+    /// Whether dithering is enabled when binding the draw buffer,
+    /// see the `dither` attribute.
+    pub fn dither(&self) -> bool {
+        self.attributes.dither
+    }
+
+    /// Changes whether dithering is enabled, applying it right away
+    /// only if the draw buffer is bound.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn set_dither(&mut self, dither: bool) {
+        self.attributes.dither = dither;
+
+        let mut bound = 0;
+        unsafe {
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut bound);
+        }
+
+        if self.framebuffer != 0 && bound as GLuint == self.framebuffer {
+            self.apply_dither();
+        }
+    }
+
+    /// Enables or disables `GL_DITHER` according to the `dither`
+    /// attribute. Since it's context state, `bind` does this, but
+    /// callers binding the framebuffer directly must call it.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn apply_dither(&self) {
+        unsafe {
+            if self.attributes.dither {
+                gl::Enable(gl::DITHER);
+            } else {
+                gl::Disable(gl::DITHER);
+            }
+        }
+    }
+
+    /// Re-establishes the state rendering into the draw buffer expects,
+    /// after the application ran its own GL code. Exactly this is set:
+    ///
+    ///  * The `GL_FRAMEBUFFER` binding, to our framebuffer
+    ///  * The viewport and scissor box, to `render_area` (the scissor
+    ///    test itself isn't enabled or disabled)
+    ///  * The draw buffers and read buffer, to our color attachments
+    ///    (GL and GLES 3, not for the default framebuffer)
+    ///  * `GL_PACK_ALIGNMENT` to 4 and `GL_PACK_ROW_LENGTH` to 0 (where
+    ///    available), their defaults
+    ///  * The `GL_PIXEL_PACK_BUFFER` binding, to none (GL and GLES 3)
+    ///  * The color mask, to `color_mask`
+    ///  * Dithering, as in `bind`
+    ///
+    /// The crate's own helpers don't need this, they set (and restore)
+    /// what they use.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn sync_state(&self) -> Result<()> {
+        try!(self.check_allocated());
+
+        let area = self.render_area();
+        let (is_gles, version) = get_gl_version();
+        let as_gl = |value: bool| if value { gl::TRUE } else { gl::FALSE };
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::Viewport(0, 0, area.width, area.height);
+            gl::Scissor(0, 0, area.width, area.height);
+
+            if version >= 30 && !self.is_default_framebuffer && !self.attributes.no_attachments {
+                let draw_buffers: Vec<GLenum> = (0..self.extra_color_render_buffers.len() as GLenum + 1)
+                    .map(|i| gl::COLOR_ATTACHMENT0 + i)
+                    .collect();
+                gl::DrawBuffers(draw_buffers.len() as GLsizei, draw_buffers.as_ptr());
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            }
+
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+
+            if !is_gles || version >= 30 {
+                gl::PixelStorei(PACK_ROW_LENGTH, 0);
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            }
+
+            gl::ColorMask(as_gl(self.color_mask[0]), as_gl(self.color_mask[1]),
+                          as_gl(self.color_mask[2]), as_gl(self.color_mask[3]));
+        }
+
+        self.apply_dither();
+
+        check_gl_error("Error restoring the draw buffer state")
+    }
+
+    /// Creates `count` draw buffers with the same size and configuration,
+    /// checking capabilities and making the context current only once.
+    ///
+    /// If any allocation fails, the already created buffers are deleted.
+    pub fn new_batch<S: ToGLSize>(context: &GLContext, size: S, count: usize)
+        -> Result<Vec<DrawBuffer>> {
+
+        let size = try!(size.to_gl_size());
+        let attrs = context.borrow_attributes();
+
+        try!(validate_request(&size, attrs, context.borrow_capabilities()));
+        let samples = DrawBuffer::check_capabilities(attrs, context.borrow_capabilities());
+
+        try!(context.make_current());
+
+        let mut draw_buffers = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            // NOTE: On failure the vector is dropped with the context
+            // still current, so the previous buffers are properly deleted.
+            let mut draw_buffer = try!(DrawBuffer::allocate(size, attrs, context.borrow_capabilities(), samples));
+            draw_buffer.owner = Some(deletion_queue_of(context));
+            draw_buffers.push(draw_buffer);
+        }
+
+        Ok(draw_buffers)
+    }
+
+    /// Wraps the default framebuffer (framebuffer 0, usually the window
+    /// back buffer) of `context`, so the helpers like `clear`,
+    /// `read_pixels` or `render_area` work the same onscreen.
+    ///
+    /// Nothing is allocated, and the framebuffer is never deleted.
+    /// The color buffer is assumed to be RGBA8, and the depth and
+    /// stencil formats are guessed from their amount of bits.
+    ///
+    /// Operations replacing attachments fail with such a draw buffer,
+    /// and `resize` only records the new size (the window system owns
+    /// the storage).
+    pub fn default_framebuffer<S: ToGLSize>(context: &GLContext, size: S)
+        -> Result<DrawBuffer> {
+        let size = try!(size.to_gl_size());
+
+        try!(context.make_current());
+
+        let mut draw_buffer = DrawBuffer::empty(size);
+        draw_buffer.attributes = *context.borrow_attributes();
+        draw_buffer.is_default_framebuffer = true;
+        draw_buffer.owner = Some(deletion_queue_of(context));
+        draw_buffer.framebuffer_owned = false;
+        draw_buffer.color_render_buffer_owned = false;
+        draw_buffer.depth_render_buffer_owned = false;
+        draw_buffer.stencil_render_buffer_owned = false;
+        draw_buffer.color_format = RGBA8;
+
+        let _guard = GLStateGuard::new();
+
+        let (is_gles, version) = get_gl_version();
+        let mut depth_bits: GLint = 0;
+        let mut stencil_bits: GLint = 0;
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            try!(draw_buffer.check_framebuffer_status());
+
+            // The *_BITS queries don't exist in core profiles
+            if !is_gles || version >= 30 {
+                gl::GetFramebufferAttachmentParameteriv(gl::FRAMEBUFFER, DEPTH,
+                                                        FRAMEBUFFER_ATTACHMENT_DEPTH_SIZE,
+                                                        &mut depth_bits);
+                gl::GetFramebufferAttachmentParameteriv(gl::FRAMEBUFFER, STENCIL,
+                                                        FRAMEBUFFER_ATTACHMENT_STENCIL_SIZE,
+                                                        &mut stencil_bits);
+            } else {
+                gl::GetIntegerv(gl::DEPTH_BITS, &mut depth_bits);
+                gl::GetIntegerv(gl::STENCIL_BITS, &mut stencil_bits);
+            }
+
+            // Missing attachments may report an error instead of zero
+            while get_error() != gl::NO_ERROR {}
+        }
+
+        draw_buffer.depth_format = match depth_bits {
+            0 => 0,
+            1...16 => gl::DEPTH_COMPONENT16,
+            _ => DEPTH_COMPONENT24,
+        };
+
+        if stencil_bits > 0 {
+            draw_buffer.stencil_format = gl::STENCIL_INDEX8;
+        }
+
+        Ok(draw_buffer)
+    }
+
+    /// Describes how the attributes were resolved into formats when the
+    /// draw buffer was allocated, including any fallback and why, like
+    /// "color: RGBA4 (RGBA8 unsupported); samples: requested 8, using 4".
+    ///
+    /// Meant for logs and bug reports, the format may change.
+    pub fn resolution_report(&self) -> String {
+        self.resolution_log.join("; ")
+    }
+
+    /// Whether this wraps the default framebuffer, see
+    /// `default_framebuffer`.
+    pub fn is_default_framebuffer(&self) -> bool {
+        self.is_default_framebuffer
+    }
+
+    /// The amount of antialiasing samples actually used,
+    /// zero if not multisampled.
+    pub fn samples(&self) -> GLsizei {
+        self.samples
+    }
+
+    /// Whether the color attachment is a texture with immutable storage
+    /// (allocated with `glTexStorage2D`). This is the case for color
+    /// textures created by the draw buffer if GL 4.2 or GLES 3.0 is
+    /// available.
+    pub fn has_immutable_color_storage(&self) -> bool {
+        self.color_texture_storage == Some(TextureStorage::Immutable)
+    }
+
+    /// The format of the color render buffer.
+    pub fn color_format(&self) -> GLenum {
+        self.color_format
+    }
+
+    /// The format of the stencil buffer, or zero if there's no stencil.
+    pub fn stencil_format(&self) -> GLenum {
+        self.stencil_format
+    }
+
+    /// Makes `context` current and deletes the GL objects, so dropping
+    /// the draw buffer doesn't depend on which context is current.
+    ///
+    /// `context` must be the one the draw buffer was created with.
+    pub fn destroy(mut self, context: &GLContext) -> Result<()> {
+        if let Some((id, _)) = self.owner {
+            if id != deletion_queue_of(context).0 {
+                return Err(GLContextError::InvalidRequest("The draw buffer belongs to another context"));
+            }
+        }
+
+        try!(context.make_current());
+
+        self.delete_objects();
+
+        Ok(())
+    }
+
+    /// Reads a single RGBA pixel from the color buffer, clamping
+    /// the coordinates into bounds. This is meant for picking, where
+    /// a full readback is overkill.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_pixel(&self, x: i32, y: i32) -> Result<[u8; 4]> {
+        try!(self.check_allocated());
+
+        if self.is_empty() {
+            return Err(GLContextError::InvalidOperation("The draw buffer is empty"));
+        }
+
+        let x = cmp::max(0, cmp::min(x, self.size.width - 1));
+        let y = cmp::max(0, cmp::min(y, self.size.height - 1));
+
+        let mut pixel = [0u8; 4];
+
+        let _guard = GLStateGuard::new();
+
+        let framebuffer = try!(self.read_framebuffer());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(x, y, 1, 1, gl::RGBA, gl::UNSIGNED_BYTE,
+                           pixel.as_mut_ptr() as *mut _);
+        }
+
+        try!(check_gl_error("glReadPixels"));
+
+        Ok(pixel)
+    }
+
+    /// Whether the draw buffer has no pixels (a zero width or height).
+    /// Rendering into it has no effect, and reads return no data.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.size.width == 0 || self.size.height == 0
+    }
+
+    #[inline(always)]
+    pub fn get_framebuffer(&self) -> GLuint {
+        self.framebuffer
+    }
+
+    /// The color texture, or zero if the color attachment is a
+    /// render buffer.
+    #[inline(always)]
+    pub fn get_color_texture(&self) -> GLuint {
+        self.color_texture
+    }
+
+    /// The current size of the draw buffer.
+    pub fn size(&self) -> Size2D<i32> {
+        self.size
+    }
+
+    /// Resizes the draw buffer, reallocating the storage of every
+    /// render buffer. Their contents are undefined afterwards.
+    ///
+    /// Externally provided color buffers can't be resized. Color
+    /// textures with immutable storage are replaced by a new texture
+    /// (see `get_color_texture`).
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn resize<S: ToGLSize>(&mut self, size: S) -> Result<()> {
+        let size = try!(size.to_gl_size());
+
+        if size == self.size && size == self.depth_stencil_size {
+            return Ok(());
+        }
+
+        if self.is_default_framebuffer {
+            self.size = size;
+            self.depth_stencil_size = size;
+            self.contents_defined.set(false);
+            return Ok(());
+        }
+
+        // Nothing to reallocate yet. As with any resize, the
+        // contents (here, a deferred clear) are lost.
+        if !self.is_allocated() {
+            self.size = size;
+            self.depth_stencil_size = size;
+            self.pending_clear.set(None);
+            return Ok(());
+        }
+
+        let external_texture = self.color_texture != 0 && self.color_texture_storage.is_none();
+        if external_texture || !self.color_render_buffer_owned {
+            return Err(GLContextError::InvalidOperation("Can't resize an externally provided color buffer"));
+        }
+
+        if self.depth_texture != 0 {
+            return Err(GLContextError::InvalidOperation("Can't resize an externally provided depth texture"));
+        }
+
+        self.size = size;
+        self.depth_stencil_size = size;
+        self.contents_defined.set(false);
+
+        // It's recreated with the new size on the next resolve
+        if let Some((framebuffer, render_buffer)) = self.resolve_target.get() {
+            delete_blit_target(framebuffer, render_buffer);
+            self.resolve_target.set(None);
+        }
+
+        if self.attributes.no_attachments {
+            return self.set_default_framebuffer_parameters();
+        }
+
+        match self.color_texture_storage {
+            Some(TextureStorage::Immutable) => {
+                // Immutable storage can't be reallocated, so replace the texture
+                unsafe {
+                    gl::DeleteTextures(1, &self.color_texture);
+                }
+
+                let (texture, _) = create_color_texture(&self.size, self.color_format,
+                                                        &self.attributes.color_texture_parameters);
+                self.color_texture = texture;
+
+                unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+                    gl::FramebufferTexture2D(gl::FRAMEBUFFER,
+                                             gl::COLOR_ATTACHMENT0,
+                                             gl::TEXTURE_2D,
+                                             self.color_texture,
+                                             0);
+                }
+            }
+            Some(TextureStorage::Mutable) => {
+                unsafe {
+                    gl::BindTexture(gl::TEXTURE_2D, self.color_texture);
+                    allocate_color_texture(TextureStorage::Mutable, self.color_format, &self.size);
+                    gl::BindTexture(gl::TEXTURE_2D, 0);
+                }
+            }
+            None => {
+                resize_render_buffer(self.color_render_buffer, self.color_format, &self.size, self.samples);
+            }
+        }
+
+        for &render_buffer in self.extra_color_render_buffers.iter() {
+            resize_render_buffer(render_buffer, self.color_format, &self.size, self.samples);
+        }
+
+        if self.depth_render_buffer != 0 {
+            resize_render_buffer(self.depth_render_buffer, self.depth_format, &self.size, self.samples);
+        }
+
+        if self.stencil_render_buffer != 0 {
+            resize_render_buffer(self.stencil_render_buffer, self.stencil_format, &self.size, self.samples);
+        }
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        }
+
+        self.check_framebuffer_status()
+    }
+
+    /// Resizes only the depth and stencil buffers. Rendering is then
+    /// limited to the intersection of the color and depth/stencil
+    /// sizes (see `render_area`).
+    ///
+    /// Fails if the implementation requires every attachment to have
+    /// the same size (GLES2).
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn resize_depth_stencil<S: ToGLSize>(&mut self, size: S) -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.ensure_allocated());
+
+        let size = try!(size.to_gl_size());
+
+        if size == self.depth_stencil_size {
+            return Ok(());
+        }
+
+        if size != self.size && !GLFeature::is_supported(GLFeature::MixedAttachmentSizes) {
+            return Err(GLContextError::Unsupported("Attachments of different sizes are not supported"));
+        }
+
+        if self.depth_texture != 0 {
+            return Err(GLContextError::InvalidOperation("Can't resize an externally provided depth texture"));
+        }
+
+        if !self.depth_render_buffer_owned || !self.stencil_render_buffer_owned {
+            return Err(GLContextError::InvalidOperation("Can't resize externally provided depth or stencil buffers"));
+        }
+
+        self.depth_stencil_size = size;
+        self.contents_defined.set(false);
+
+        if self.depth_render_buffer != 0 {
+            resize_render_buffer(self.depth_render_buffer, self.depth_format,
+                                 &self.depth_stencil_size, self.samples);
+        }
+
+        if self.stencil_render_buffer != 0 {
+            resize_render_buffer(self.stencil_render_buffer, self.stencil_format,
+                                 &self.depth_stencil_size, self.samples);
+        }
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        }
+
+        self.check_framebuffer_status()
+    }
+
+    /// The area that can actually be rendered to: the intersection
+    /// of every attachment.
+    pub fn render_area(&self) -> Size2D<i32> {
+        if self.depth_format == 0 && self.stencil_format == 0 {
+            return self.size;
+        }
+
+        Size2D(cmp::min(self.size.width, self.depth_stencil_size.width),
+               cmp::min(self.size.height, self.depth_stencil_size.height))
+    }
+
+    /// Temporarily resizes the draw buffer to `size` while `f` runs,
+    /// restoring the original size afterwards (including a separate
+    /// depth/stencil size, see `resize_depth_stencil`), even if `f`
+    /// panics.
+    ///
+    /// As with `resize`, the contents of every attachment are lost,
+    /// both when resizing and when restoring.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn with_temporary_size<S, F, R>(&mut self, size: S, f: F) -> Result<R>
+        where S: ToGLSize, F: FnOnce(&mut DrawBuffer) -> R {
+        let original_size = self.size;
+        let original_depth_stencil_size = self.depth_stencil_size;
+
+        try!(self.resize(size));
+
+        let mut guard = RestoreSizeGuard {
+            draw_buffer: self,
+            size: original_size,
+            depth_stencil_size: original_depth_stencil_size,
+            restored: false,
+        };
+
+        let result = f(&mut *guard.draw_buffer);
+
+        // Restore explicitly on the regular path, so we can report errors
+        guard.restored = true;
+        try!(guard.restore());
+
+        Ok(result)
+    }
+
+    /// Checks whether the framebuffer is complete, restoring
+    /// the previous framebuffer binding afterwards. Draw buffers
+    /// that aren't allocated yet aren't complete.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn is_complete(&self) -> bool {
+        if !self.is_allocated() || self.detached {
+            return false;
+        }
+
+        unsafe {
+            let mut previous: GLint = 0;
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous);
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            let complete = self.check_framebuffer_status().is_ok();
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous as GLuint);
+
+            complete
+        }
+    }
+
+    /// Clears the draw buffer with `color`, depth 1.0 and stencil 0.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn clear(&self, color: [GLfloat; 4]) -> Result<()> {
+        self.clear_with(color, 1.0, 0)
+    }
+
+    /// Clears every attachment of the draw buffer with the given values.
+    /// Depth and stencil values are ignored if there's no such attachment.
+    ///
+    /// The previous clear values are restored.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn clear_with(&self, color: [GLfloat; 4], depth: GLfloat, stencil: GLint)
+        -> Result<()> {
+        if !self.is_allocated() {
+            self.pending_clear.set(Some((color, depth, stencil)));
+            return Ok(());
+        }
+
+        let _guard = GLStateGuard::new();
+        let _mask_guard = ColorMaskGuard::new(self.color_mask);
+
+        unsafe {
+            let mut previous_color: [GLfloat; 4] = [0.0; 4];
+            let mut previous_depth: GLfloat = 0.0;
+            let mut previous_stencil: GLint = 0;
+            gl::GetFloatv(gl::COLOR_CLEAR_VALUE, previous_color.as_mut_ptr());
+            gl::GetFloatv(gl::DEPTH_CLEAR_VALUE, &mut previous_depth);
+            gl::GetIntegerv(gl::STENCIL_CLEAR_VALUE, &mut previous_stencil);
+
+            let mut mask: GLbitfield = gl::COLOR_BUFFER_BIT;
+
+            gl::ClearColor(color[0], color[1], color[2], color[3]);
+
+            if self.depth_format != 0 {
+                clear_depth(depth);
+                mask |= gl::DEPTH_BUFFER_BIT;
+            }
+
+            if self.stencil_format != 0 {
+                gl::ClearStencil(stencil);
+                mask |= gl::STENCIL_BUFFER_BIT;
+            }
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::Clear(mask);
+
+            gl::ClearColor(previous_color[0], previous_color[1],
+                           previous_color[2], previous_color[3]);
+            clear_depth(previous_depth);
+            gl::ClearStencil(previous_stencil);
+        }
+
+        try!(check_gl_error("glClear"));
+
+        self.contents_defined.set(true);
+
+        Ok(())
+    }
+
+    /// Whether the contents of the draw buffer are defined, that is,
+    /// whether it was cleared or rendered to since it was allocated or
+    /// resized. Reading undefined contents returns garbage.
+    ///
+    /// Rendering done directly through GL isn't tracked, so callers doing
+    /// so should call `mark_contents_defined`.
+    pub fn contents_defined(&self) -> bool {
+        self.contents_defined.get()
+    }
+
+    /// Records that the contents were written outside of `clear`.
+    pub fn mark_contents_defined(&self) {
+        self.contents_defined.set(true);
+    }
+
+    /// Tells the driver the contents of the given region of the color
+    /// attachments won't be needed anymore (they'll be overwritten), so
+    /// tiled GPUs can skip storing them. The region contents are
+    /// undefined afterwards.
+    ///
+    /// This uses `glInvalidateSubFramebuffer` (GL 4.3 / GLES 3.0), and
+    /// is a no-op where it's not supported, since it's only a hint.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn invalidate_region(&self, x: i32, y: i32, width: i32, height: i32)
+        -> Result<()> {
+        if x < 0 || y < 0 || width < 0 || height < 0 ||
+           x > self.size.width || width > self.size.width - x ||
+           y > self.size.height || height > self.size.height - y {
+            return Err(GLContextError::InvalidRequest("The region is out of bounds"));
+        }
+
+        if !self.is_allocated() || self.detached || self.attributes.no_attachments ||
+           width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        if !GLFeature::is_supported(GLFeature::InvalidateFramebuffer) {
+            return Ok(());
+        }
+
+        // The default framebuffer names its buffers differently
+        let attachments: Vec<GLenum> = if self.is_default_framebuffer {
+            vec![COLOR]
+        } else {
+            (0..self.extra_color_render_buffers.len() as GLenum + 1)
+                .map(|i| gl::COLOR_ATTACHMENT0 + i)
+                .collect()
+        };
+
+        let _guard = GLStateGuard::new();
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::InvalidateSubFramebuffer(gl::FRAMEBUFFER, attachments.len() as GLsizei,
+                                         attachments.as_ptr(), x, y, width, height);
+        }
+
+        if width == self.size.width && height == self.size.height {
+            self.contents_defined.set(false);
+        }
+
+        check_gl_error("glInvalidateSubFramebuffer")
+    }
+
+    /// Sets whether `read_pixels` flips the rows vertically, so the
+    /// first row is the top one. Defaults to false.
+    pub fn set_flip_y(&mut self, flip_y: bool) {
+        self.flip_y = flip_y;
+    }
+
+    pub fn flip_y(&self) -> bool {
+        self.flip_y
+    }
+
+    /// Reads the whole color buffer as RGBA, flipping the rows
+    /// if `set_flip_y(true)` was called. Multisampled buffers are
+    /// resolved first (see `resolve_and_read`).
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_pixels(&self) -> Result<Vec<u8>> {
+        self.read_pixels_flipped(self.flip_y)
+    }
+
+    /// Like `read_pixels`, but overriding the stored `flip_y` setting.
+    pub fn read_pixels_flipped(&self, flip_y: bool) -> Result<Vec<u8>> {
+        try!(self.check_allocated());
+
+        if !self.contents_defined.get() {
+            warn!("Reading the contents of a draw buffer that wasn't cleared or rendered to");
+        }
+
+        let width = self.size.width as usize;
+        let height = self.size.height as usize;
+
+        let mut pixels: Vec<u8> = vec![0; width * height * 4];
+
+        let _guard = GLStateGuard::new();
+
+        let framebuffer = try!(self.read_framebuffer());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                           gl::RGBA, gl::UNSIGNED_BYTE,
+                           pixels.as_mut_ptr() as *mut _);
+        }
+
+        try!(check_gl_error("glReadPixels"));
+
+        if flip_y {
+            pixels::flip_rows(&mut pixels, width * 4);
+        }
+
+        Ok(pixels)
+    }
+
+    /// Reads the color buffer in the layout embedders can rely on,
+    /// whatever the `flip_y` setting: RGBA8, one byte per channel in
+    /// R, G, B, A order, with the first row being the top one (top-left
+    /// origin). Rows are tightly packed, so the returned stride (in
+    /// bytes) is always `width * 4`. The returned size is the size of
+    /// the draw buffer.
+    ///
+    /// Alpha is as stored, that is, premultiplied if the draw buffer
+    /// was created with `premultiplied_alpha`.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn as_rgba_bytes(&self) -> Result<(Vec<u8>, usize, Size2D<i32>)> {
+        let pixels = try!(self.read_pixels_flipped(true));
+        Ok((pixels, self.size.width as usize * 4, self.size))
+    }
+
+    /// Captures the color buffer into a `Snapshot`, with the rows top to
+    /// bottom (regardless of `flip_y`) and premultiplied according to the
+    /// `premultiplied_alpha` attribute.
+    ///
+    /// Pending rendering commands are flushed first.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        unsafe {
+            gl::Flush();
+        }
+
+        let pixels = try!(self.read_pixels_flipped(true));
+
+        Ok(Snapshot {
+            width: self.size.width,
+            height: self.size.height,
+            format: pixels::PixelLayout {
+                channels: pixels::ChannelOrder::RGBA,
+                premultiplied: self.attributes.premultiplied_alpha,
+                top_down: true,
+            },
+            pixels: pixels,
+        })
+    }
+
+    /// Reads the color buffer in the format the implementation prefers
+    /// (`GL_IMPLEMENTATION_COLOR_READ_FORMAT` and `_TYPE`), which avoids
+    /// conversions inside the driver. Returns the format, the type, and
+    /// the tightly packed rows, bottom to top.
+    ///
+    /// The format varies between implementations (and color formats),
+    /// so callers must handle whatever is returned. If the preferred
+    /// format isn't known (or can't be queried, before GL 4.1), this
+    /// reads `GL_RGBA`/`GL_UNSIGNED_BYTE`.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_pixels_native(&self) -> Result<(GLenum, GLenum, Vec<u8>)> {
+        try!(self.check_allocated());
+
+        let _guard = GLStateGuard::new();
+
+        let mut format = 0;
+        let mut ty = 0;
+
+        // NOTE: The preferred format is queried for the resolved
+        // framebuffer, since that's the one we read
+        let framebuffer = try!(self.read_framebuffer());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::GetIntegerv(IMPLEMENTATION_COLOR_READ_FORMAT, &mut format);
+            gl::GetIntegerv(IMPLEMENTATION_COLOR_READ_TYPE, &mut ty);
+
+            // Discard the error if the query isn't supported
+            while get_error() != gl::NO_ERROR {}
+        }
+
+        let (format, ty) = (format as GLenum, ty as GLenum);
+        let (format, ty, pixel_size) = match read_pixel_size(format, ty) {
+            Some(pixel_size) => (format, ty, pixel_size),
+            None => (gl::RGBA, gl::UNSIGNED_BYTE, 4),
+        };
+
+        let mut pixels: Vec<u8> = vec![0; self.size.width as usize * self.size.height as usize * pixel_size];
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                           format, ty, pixels.as_mut_ptr() as *mut _);
+        }
+
+        try!(check_gl_error("glReadPixels"));
+
+        Ok((format, ty, pixels))
+    }
+
+    /// Reads the color buffer converted to `layout`, in one call.
+    ///
+    /// The read is done as BGRA when that's requested and the context
+    /// supports it, so only the remaining differences (row order and
+    /// premultiplication) are converted on the CPU.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn capture_as(&self, context: &GLContext, layout: pixels::PixelLayout)
+        -> Result<CapturedFrame> {
+        try!(self.check_allocated());
+
+        if is_integer_format(self.color_format) || is_float_format(self.color_format) {
+            return Err(GLContextError::InvalidOperation("Can't capture a non-normalized color buffer"));
+        }
+
+        let read_bgra = layout.channels == pixels::ChannelOrder::BGRA &&
+                        context.borrow_capabilities().extensions.contains(EXT_BGRA_READ);
+
+        let pixels = if read_bgra {
+            let mut pixels: Vec<u8> = vec![0; self.size.width as usize * self.size.height as usize * 4];
+
+            let _guard = GLStateGuard::new();
+
+            let framebuffer = try!(self.read_framebuffer());
+
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+                gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                               BGRA, gl::UNSIGNED_BYTE,
+                               pixels.as_mut_ptr() as *mut _);
+            }
+
+            try!(check_gl_error("glReadPixels"));
+
+            if layout.top_down {
+                pixels::flip_rows(&mut pixels, self.size.width as usize * 4);
+            }
+
+            pixels
+        } else {
+            try!(self.read_pixels_flipped(layout.top_down))
+        };
+
+        let read_layout = pixels::PixelLayout {
+            channels: if read_bgra { pixels::ChannelOrder::BGRA } else { pixels::ChannelOrder::RGBA },
+            premultiplied: self.attributes.premultiplied_alpha,
+            top_down: layout.top_down,
+        };
+
+        let pixels = if read_layout == layout {
+            pixels
+        } else {
+            pixels::convert(&pixels, read_layout, layout, self.size)
+        };
+
+        Ok(CapturedFrame {
+            width: self.size.width,
+            height: self.size.height,
+            stride: self.size.width as usize * layout.channels.bytes_per_pixel(),
+            layout: layout,
+            opaque: !self.attributes.alpha,
+            pixels: pixels,
+        })
+    }
+
+    /// Like `read_pixels`, but when possible returns a view of a mapped
+    /// pixel pack buffer instead of copying into a `Vec`. This is the
+    /// cheapest readback on unified memory GPUs.
+    ///
+    /// The data is copied instead if mapping isn't supported (GLES2), or
+    /// if `flip_y` is set, since the mapping can't be modified.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current,
+    /// and the result must be dropped while it's still current.
+    pub fn read_pixels_mapped(&self) -> Result<MappedReadback> {
+        try!(self.check_allocated());
+
+        // NOTE: Zero-size buffers can't be mapped
+        if !self.flip_y && !self.is_empty() {
+            let _guard = GLStateGuard::new();
+
+            let framebuffer = try!(self.read_framebuffer());
+
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            }
+
+            if let Some(readback) = try!(mapped_readback::read_and_map(&self.size)) {
+                return Ok(readback);
+            }
+        }
+
+        Ok(mapped_readback::from_vec(try!(self.read_pixels())))
+    }
+
+    /// Reads the color buffer as RGBA floats (bottom row first), so values
+    /// of float color buffers outside of [0, 1] survive the readback, as
+    /// required by `WEBGL_color_buffer_float`.
+    ///
+    /// On desktop GL this disables `GL_CLAMP_READ_COLOR` during the read
+    /// (its default only clamps fixed-point buffers, but the application
+    /// may have changed it). GLES doesn't have `glClampColor`, and never
+    /// clamps reads from float buffers.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_pixels_float(&self) -> Result<Vec<GLfloat>> {
+        try!(self.check_allocated());
+
+        if is_integer_format(self.color_format) {
+            return Err(GLContextError::InvalidOperation("Integer color buffers can't be read as floats"));
+        }
+
+        let mut pixels: Vec<GLfloat> = vec![0.0; self.size.width as usize * self.size.height as usize * 4];
+
+        let (is_gles, _) = get_gl_version();
+        let unclamp = !is_gles && is_float_format(self.color_format);
+
+        let _guard = GLStateGuard::new();
+
+        let framebuffer = try!(self.read_framebuffer());
+
+        unsafe {
+            let mut previous_clamp: GLint = 0;
+
+            if unclamp {
+                gl::GetIntegerv(CLAMP_READ_COLOR, &mut previous_clamp);
+                gl::ClampColor(CLAMP_READ_COLOR, gl::FALSE as GLenum);
+            }
+
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                           gl::RGBA, gl::FLOAT,
+                           pixels.as_mut_ptr() as *mut _);
+
+            if unclamp {
+                gl::ClampColor(CLAMP_READ_COLOR, previous_clamp as GLenum);
+            }
+        }
+
+        try!(check_gl_error("glReadPixels"));
+
+        Ok(pixels)
+    }
+
+    /// Reads an integer color buffer (like `GL_R32UI` object id buffers) as
+    /// RGBA integers, bottom row first. Channels missing from the format
+    /// are returned as 0 (1 for alpha). Signed values are reinterpreted
+    /// as `u32`.
+    ///
+    /// Fails if the color buffer isn't an integer one.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_pixels_integer(&self) -> Result<Vec<u32>> {
+        try!(self.check_allocated());
+
+        if !is_integer_format(self.color_format) {
+            return Err(GLContextError::InvalidOperation("The color buffer doesn't have an integer format"));
+        }
+
+        // The only combinations GLES 3.0 guarantees
+        let data_type = if is_unsigned_integer_format(self.color_format) {
+            gl::UNSIGNED_INT
+        } else {
+            gl::INT
+        };
+
+        let mut pixels: Vec<u32> = vec![0; self.size.width as usize * self.size.height as usize * 4];
+
+        let _guard = GLStateGuard::new();
+
+        let framebuffer = try!(self.read_framebuffer());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+            gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                           RGBA_INTEGER, data_type,
+                           pixels.as_mut_ptr() as *mut _);
+        }
+
+        try!(check_gl_error("glReadPixels"));
+
+        Ok(pixels)
+    }
+
+    /// Reads the color buffer as RGBA directly into `destination`
+    /// (usually a shared memory segment), with rows `stride` bytes
+    /// apart (bottom row first).
+    ///
+    /// `stride` must be a multiple of 4 and at least `width * 4`.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_pixels_into_shmem(&self, destination: &mut [u8], stride: usize)
+        -> Result<()> {
+        try!(self.check_allocated());
+
+        let width = self.size.width as usize;
+        let height = self.size.height as usize;
+        let row_bytes = width * 4;
+
+        if height == 0 || width == 0 {
+            return Ok(());
+        }
+
+        if stride < row_bytes || stride % 4 != 0 {
+            return Err(GLContextError::InvalidRequest("Invalid stride"));
+        }
+
+        if destination.len() < stride * (height - 1) + row_bytes {
+            return Err(GLContextError::InvalidRequest("The destination is too small"));
+        }
+
+        let (is_gles, version) = get_gl_version();
+        let has_row_length = !is_gles || version >= 30;
+
+        let _guard = GLStateGuard::new();
+
+        let framebuffer = try!(self.read_framebuffer());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+
+            if stride == row_bytes {
+                gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                               gl::RGBA, gl::UNSIGNED_BYTE,
+                               destination.as_mut_ptr() as *mut _);
+            } else if has_row_length {
+                gl::PixelStorei(PACK_ROW_LENGTH, (stride / 4) as GLint);
+                gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                               gl::RGBA, gl::UNSIGNED_BYTE,
+                               destination.as_mut_ptr() as *mut _);
+            } else {
+                // GLES2 doesn't have GL_PACK_ROW_LENGTH, so read row by row
+                for row in 0..height {
+                    gl::ReadPixels(0, row as GLint, self.size.width, 1,
+                                   gl::RGBA, gl::UNSIGNED_BYTE,
+                                   destination[row * stride..].as_mut_ptr() as *mut _);
+                }
+            }
+        }
+
+        check_gl_error("glReadPixels")
+    }
+
+    /// Reads only the alpha channel of the color buffer, returning
+    /// `width * height` bytes (bottom row first).
+    ///
+    /// If the draw buffer was created without alpha, every value is 255.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_alpha(&self) -> Result<Vec<u8>> {
+        try!(self.check_allocated());
+
+        let pixel_count = self.size.width as usize * self.size.height as usize;
+
+        if !self.attributes.alpha {
+            return Ok(vec![255; pixel_count]);
+        }
+
+        // Only desktop GL guarantees GL_ALPHA reads, GLES only
+        // guarantees GL_RGBA
+        let (is_gles, _) = get_gl_version();
+        if is_gles {
+            let pixels = try!(self.read_pixels_flipped(false));
+            return Ok(pixels.chunks(4).map(|pixel| pixel[3]).collect());
+        }
+
+        let mut alpha: Vec<u8> = vec![0; pixel_count];
+
+        let _guard = GLStateGuard::new();
+
+        let framebuffer = try!(self.read_framebuffer());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                           gl::ALPHA, gl::UNSIGNED_BYTE,
+                           alpha.as_mut_ptr() as *mut _);
+        }
+
+        try!(check_gl_error("glReadPixels"));
+
+        Ok(alpha)
+    }
+
+    /// Blits the color buffer into `dest`, converting between color formats
+    /// (e.g. RGBA16F to RGBA8) and scaling linearly if the sizes differ.
+    ///
+    /// Fails if the conversion isn't allowed by `glBlitFramebuffer`, which
+    /// is the case for integer to non-integer formats (and vice versa).
+    ///
+    /// On GLES2, which lacks `glBlitFramebuffer`, this draws a quad instead.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn blit_convert(&self, dest: &DrawBuffer) -> Result<()> {
+        try!(self.check_allocated());
+        try!(dest.check_allocated());
+
+        let source_is_integer = is_integer_format(self.color_format);
+        if source_is_integer != is_integer_format(dest.color_format) {
+            return Err(GLContextError::InvalidOperation("Can't blit between integer and non-integer color formats"));
+        }
+
+        // Integer formats can't be filtered
+        let filter = if self.size == dest.size || source_is_integer {
+            gl::NEAREST
+        } else {
+            gl::LINEAR
+        };
+
+        let _mask_guard = ColorMaskGuard::new(dest.color_mask);
+
+        copy_color(self.framebuffer, &self.size, self.samples,
+                   dest.framebuffer, &dest.size, filter, CompositeOp::Copy)
+    }
+
+    /// Sets which color channels `clear` (and the helpers writing into
+    /// the draw buffer, like `blit_convert`) write. The previous mask is
+    /// restored afterwards, the one set with `glColorMask` for the
+    /// application's own rendering isn't affected.
+    ///
+    /// Defaults to writing every channel.
+    pub fn set_color_mask(&mut self, red: bool, green: bool, blue: bool, alpha: bool) {
+        self.color_mask = [red, green, blue, alpha];
+    }
+
+    pub fn color_mask(&self) -> [bool; 4] {
+        self.color_mask
+    }
+
+    /// Sets how `blit_to_default` composites the contents. Defaults to
+    /// `CompositeOp::Copy`.
+    pub fn set_composite_op(&mut self, op: CompositeOp) {
+        self.composite_op = op;
+    }
+
+    pub fn composite_op(&self) -> CompositeOp {
+        self.composite_op
+    }
+
+    /// Composites the color buffer into the default framebuffer of the
+    /// current context (whose size is `dest_size`) according to the
+    /// composite op, scaling it if the sizes differ.
+    ///
+    /// `glBlitFramebuffer` ignores the blend state, so blending (and
+    /// GLES2, which lacks it) draws a textured quad instead, restoring
+    /// the previous blend state. Multisampled draw buffers can't be
+    /// blended nor scaled.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn blit_to_default(&self, dest_size: Size2D<i32>) -> Result<()> {
+        try!(self.check_allocated());
+
+        let is_integer = is_integer_format(self.color_format);
+
+        if is_integer && self.composite_op != CompositeOp::Copy {
+            return Err(GLContextError::InvalidOperation("Integer color buffers can't be blended"));
+        }
+
+        // Integer formats can't be filtered
+        let filter = if dest_size == self.size || is_integer {
+            gl::NEAREST
+        } else {
+            gl::LINEAR
+        };
+
+        copy_color(self.framebuffer, &self.size, self.samples,
+                   0, &dest_size, filter, self.composite_op)
+    }
+
+    /// Reads the color buffer downscaled (or upscaled) to `width`x`height`,
+    /// with linear filtering. Useful for thumbnails. The rows are flipped
+    /// according to `flip_y`.
+    ///
+    /// Makes `context` current if needed.
+    pub fn read_pixels_scaled(&self, context: &GLContext, width: i32, height: i32)
+        -> Result<Vec<u8>> {
+        try!(self.check_allocated());
+
+        let size = Size2D(width, height);
+
+        if width <= 0 || height <= 0 {
+            return Err(GLContextError::InvalidRequest("Invalid size"));
+        }
+
+        if self.is_empty() {
+            return Err(GLContextError::InvalidOperation("The draw buffer is empty"));
+        }
+
+        try!(context.borrow_capabilities().check_size(&size));
+
+        if is_integer_format(self.color_format) {
+            return Err(GLContextError::InvalidOperation("Integer color formats can't be scaled"));
+        }
+
+        try!(context.make_current());
+
+        let _guard = GLStateGuard::new();
+
+        // Multisampled buffers can't be scaled while resolving,
+        // so resolve them first at their own size.
+        let source = try!(self.read_framebuffer());
+
+        let result = create_blit_target(&size).and_then(|(framebuffer, render_buffer)| {
+            let mut pixels: Vec<u8> = vec![0; width as usize * height as usize * 4];
+
+            if let Err(err) = copy_color(source, &self.size, 0, framebuffer, &size,
+                                         gl::LINEAR, CompositeOp::Copy) {
+                delete_blit_target(framebuffer, render_buffer);
+                return Err(err);
+            }
+
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+                gl::ReadPixels(0, 0, width, height,
+                               gl::RGBA, gl::UNSIGNED_BYTE,
+                               pixels.as_mut_ptr() as *mut _);
+            }
+
+            delete_blit_target(framebuffer, render_buffer);
+
+            try!(check_gl_error("glReadPixels"));
+
+            if self.flip_y {
+                pixels::flip_rows(&mut pixels, width as usize * 4);
+            }
+
+            Ok(pixels)
+        });
+
+        result
+    }
+
+    /// Resolves the multisampled color buffer into a single sampled
+    /// framebuffer and reads it, the antialiased counterpart of
+    /// `read_pixels` (rows are flipped according to `flip_y` too).
+    ///
+    /// The single sampled framebuffer is created on the first call
+    /// and reused afterwards.
+    ///
+    /// Makes `context` current if needed. Non-multisampled draw buffers
+    /// are read directly. Apart from that, it's `read_pixels`, which
+    /// resolves too.
+    pub fn resolve_and_read(&self, context: &GLContext) -> Result<Vec<u8>> {
+        try!(self.check_allocated());
+
+        try!(context.make_current());
+
+        self.read_pixels()
+    }
+
+    /// Returns the average color of the draw buffer, as RGBA in [0, 1],
+    /// without reading back every pixel.
+    ///
+    /// For color textures created by the draw buffer (with mutable
+    /// storage) the average is the smallest mip level, generated by the
+    /// GPU. Otherwise the contents are halved repeatedly with linear
+    /// filtering and the last few pixels are averaged on the CPU.
+    ///
+    /// Makes `context` current if needed.
+    pub fn average_color(&self, context: &GLContext) -> Result<[f32; 4]> {
+        try!(self.check_allocated());
+
+        try!(context.make_current());
+
+        if self.is_empty() {
+            return Err(GLContextError::InvalidOperation("The draw buffer is empty"));
+        }
+
+        if is_integer_format(self.color_format) {
+            return Err(GLContextError::InvalidOperation("Integer color formats can't be averaged"));
+        }
+
+        let _guard = GLStateGuard::new();
+
+        // NOTE: GLES2 can't generate mipmaps of non-power-of-two
+        // textures, nor render to (so read from) levels other than 0
+        let (is_gles, version) = get_gl_version();
+        let can_mipmap = self.color_texture_storage == Some(TextureStorage::Mutable) &&
+                         self.multiview.is_none() &&
+                         (!is_gles || version >= 30);
+
+        if can_mipmap {
+            let mut level = 0;
+            while (self.size.width >> level) > 1 || (self.size.height >> level) > 1 {
+                level += 1;
+            }
+
+            let mut framebuffer = 0;
+            let mut pixel = [0u8; 4];
+
+            unsafe {
+                gl::BindTexture(gl::TEXTURE_2D, self.color_texture);
+                gl::GenerateMipmap(gl::TEXTURE_2D);
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+
+                gl::GenFramebuffers(1, &mut framebuffer);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0,
+                                         gl::TEXTURE_2D, self.color_texture, level);
+                gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+                gl::ReadPixels(0, 0, 1, 1, gl::RGBA, gl::UNSIGNED_BYTE,
+                               pixel.as_mut_ptr() as *mut _);
+                gl::DeleteFramebuffers(1, &framebuffer);
+            }
+
+            try!(check_gl_error("Error reading the smallest mip level"));
+
+            return Ok([pixel[0] as f32 / 255.0, pixel[1] as f32 / 255.0,
+                       pixel[2] as f32 / 255.0, pixel[3] as f32 / 255.0]);
+        }
+
+        // Halving with linear filtering averages 2x2 blocks. Small enough
+        // images are cheaper (and more precise) to average on the CPU.
+        let mut size = self.size;
+        let mut source = (self.framebuffer, 0);
+        let mut samples = self.samples;
+
+        while samples > 0 || size.width as usize * size.height as usize > 256 {
+            // Multisampled buffers must be resolved before scaling
+            let next_size = if samples > 0 {
+                size
+            } else {
+                Size2D((size.width + 1) / 2, (size.height + 1) / 2)
+            };
+
+            let target = match create_blit_target(&next_size) {
+                Ok(target) => target,
+                Err(err) => {
+                    if source.1 != 0 {
+                        delete_blit_target(source.0, source.1);
+                    }
+                    return Err(err);
+                }
+            };
+
+            let result = copy_color(source.0, &size, samples,
+                                    target.0, &next_size, gl::LINEAR, CompositeOp::Copy);
+
+            if source.1 != 0 {
+                delete_blit_target(source.0, source.1);
+            }
+
+            source = target;
+
+            if let Err(err) = result {
+                delete_blit_target(source.0, source.1);
+                return Err(err);
+            }
+
+            size = next_size;
+            samples = 0;
+        }
+
+        let mut pixels: Vec<u8> = vec![0; size.width as usize * size.height as usize * 4];
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, source.0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, size.width, size.height, gl::RGBA, gl::UNSIGNED_BYTE,
+                           pixels.as_mut_ptr() as *mut _);
+        }
+
+        if source.1 != 0 {
+            delete_blit_target(source.0, source.1);
+        }
+
+        try!(check_gl_error("glReadPixels"));
+
+        let mut sums = [0u64; 4];
+        for pixel in pixels.chunks(4) {
+            for (sum, &value) in sums.iter_mut().zip(pixel.iter()) {
+                *sum += value as u64;
+            }
+        }
+
+        let count = (size.width as usize * size.height as usize) as f32 * 255.0;
+        Ok([sums[0] as f32 / count, sums[1] as f32 / count,
+            sums[2] as f32 / count, sums[3] as f32 / count])
+    }
+
+    /// Resolves the multisampled color buffer into `texture`, a caller
+    /// owned 2D texture with the same size and (on GLES) format.
+    ///
+    /// The texture size is validated on desktop GL. GLES can't query
+    /// it (until GLES 3.1), so a mismatch is only caught by the blit.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn resolve_to_texture(&self, texture: GLuint) -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.check_allocated());
+
+        if self.samples == 0 {
+            return Err(GLContextError::InvalidOperation("The draw buffer isn't multisampled"));
+        }
+
+        if texture == 0 {
+            return Err(GLContextError::InvalidRequest("Invalid texture"));
+        }
+
+        let (is_gles, _) = get_gl_version();
+
+        if !is_gles {
+            let mut previous_texture: GLint = 0;
+            let mut width: GLint = 0;
+            let mut height: GLint = 0;
+
+            unsafe {
+                gl::GetIntegerv(gl::TEXTURE_BINDING_2D, &mut previous_texture);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, TEXTURE_WIDTH, &mut width);
+                gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, TEXTURE_HEIGHT, &mut height);
+                gl::BindTexture(gl::TEXTURE_2D, previous_texture as GLuint);
+            }
+
+            if width != self.size.width || height != self.size.height {
+                return Err(GLContextError::InvalidRequest("The texture size doesn't match the draw buffer size"));
+            }
+        }
+
+        let _guard = GLStateGuard::new();
+
+        let mut framebuffer: GLuint = 0;
+
+        let status = unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(DRAW_FRAMEBUFFER, framebuffer);
+            gl::FramebufferTexture2D(DRAW_FRAMEBUFFER, gl::COLOR_ATTACHMENT0,
+                                     gl::TEXTURE_2D, texture, 0);
+            gl::CheckFramebufferStatus(DRAW_FRAMEBUFFER)
+        };
+
+        let result = if status != gl::FRAMEBUFFER_COMPLETE {
+            Err(GLContextError::IncompleteFramebuffer(status))
+        } else {
+            unsafe {
+                gl::BindFramebuffer(READ_FRAMEBUFFER, self.framebuffer);
+                gl::BlitFramebuffer(0, 0, self.size.width, self.size.height,
+                                    0, 0, self.size.width, self.size.height,
+                                    gl::COLOR_BUFFER_BIT, gl::NEAREST);
+            }
+
+            check_gl_error("glBlitFramebuffer")
+        };
+
+        unsafe {
+            gl::DeleteFramebuffers(1, &framebuffer);
+        }
+
+        result
+    }
+
+    /// Replaces the color buffer with the mip `level` of a 2D texture of
+    /// the given `format`, whose base level is `base_size`.
+    ///
+    /// The size of the draw buffer becomes the size of that mip level,
+    /// resizing the depth and stencil buffers accordingly.
+    ///
+    /// If `owned` is true the texture is deleted when the draw buffer
+    /// is dropped (or when it's replaced again).
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn set_color_texture(&mut self,
+                             texture: GLuint,
+                             format: GLenum,
+                             base_size: Size2D<i32>,
+                             level: GLint,
+                             owned: bool)
+        -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.ensure_allocated());
+
+        let size = match mip_level_size(&base_size, level) {
+            Some(size) => size,
+            None => return Err(GLContextError::InvalidRequest("The texture doesn't have the requested mip level")),
+        };
+
+        if size != self.size && self.depth_texture != 0 {
+            return Err(GLContextError::InvalidOperation("Can't resize an externally provided depth texture"));
+        }
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                        gl::COLOR_ATTACHMENT0,
+                                        gl::RENDERBUFFER,
+                                        0);
+
+            if self.color_render_buffer_owned && self.color_render_buffer != 0 {
+                gl::DeleteRenderbuffers(1, &self.color_render_buffer);
+            }
+
+            if self.color_texture_owned && self.color_texture != 0 && self.color_texture != texture {
+                gl::DeleteTextures(1, &self.color_texture);
+            }
+        }
+
+        self.color_render_buffer = 0;
+        self.color_texture = texture;
+        self.color_texture_owned = owned;
+        self.color_texture_level = level;
+        self.color_texture_storage = None;
+        self.color_format = format;
+        self.multiview = None;
+
+        if size != self.size || size != self.depth_stencil_size {
+            self.size = size;
+            self.depth_stencil_size = size;
+            self.contents_defined.set(false);
+
+            if self.depth_render_buffer != 0 {
+                resize_render_buffer(self.depth_render_buffer, self.depth_format, &self.size, self.samples);
+            }
+
+            if self.stencil_render_buffer != 0 {
+                resize_render_buffer(self.stencil_render_buffer, self.stencil_format, &self.size, self.samples);
+            }
+        }
+
+        self.attach_renderbuffers_to_framebuffer()
+    }
+
+    /// Replaces the color buffer with a 2D array `texture` of `size`,
+    /// attached as `num_views` views starting at layer `base_view`
+    /// (`GL_OVR_multiview2`), so every view is rendered in one pass.
+    ///
+    /// Every attachment of a multiview framebuffer must be multiview, so
+    /// the depth and stencil buffers are removed, and the draw buffer
+    /// must not be multisampled nor have extra color attachments.
+    ///
+    /// If `owned` is true the texture is deleted when the draw buffer
+    /// is dropped (or when it's replaced again).
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn set_color_texture_multiview(&mut self,
+                                       context: &GLContext,
+                                       texture: GLuint,
+                                       format: GLenum,
+                                       size: Size2D<i32>,
+                                       base_view: GLint,
+                                       num_views: GLsizei,
+                                       owned: bool)
+        -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.ensure_allocated());
+
+        if !context.borrow_capabilities().extensions.contains(EXT_MULTIVIEW) {
+            return Err(GLContextError::Unsupported("Multiview rendering is not supported"));
+        }
+
+        if self.samples > 0 || !self.extra_color_render_buffers.is_empty() {
+            return Err(GLContextError::InvalidOperation("Multiview draw buffers can't be multisampled or have extra color attachments"));
+        }
+
+        let mut max_views = 0;
+        unsafe {
+            gl::GetIntegerv(MAX_VIEWS_OVR, &mut max_views);
+        }
+
+        if base_view < 0 || num_views < 1 || num_views > max_views {
+            return Err(GLContextError::InvalidRequest("Invalid multiview range"));
+        }
+
+        let attach = context.get_proc_address("glFramebufferTextureMultiviewOVR");
+        if attach.is_null() {
+            return Err(GLContextError::Unsupported("Couldn't load glFramebufferTextureMultiviewOVR"));
+        }
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, 0);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, 0);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::STENCIL_ATTACHMENT, gl::RENDERBUFFER, 0);
+
+            if self.color_render_buffer_owned && self.color_render_buffer != 0 {
+                gl::DeleteRenderbuffers(1, &self.color_render_buffer);
+            }
+
+            if self.color_texture_owned && self.color_texture != 0 && self.color_texture != texture {
+                gl::DeleteTextures(1, &self.color_texture);
+            }
+
+            if self.depth_render_buffer_owned && self.depth_render_buffer != 0 {
+                gl::DeleteRenderbuffers(1, &self.depth_render_buffer);
+            }
+
+            if self.depth_texture_owned && self.depth_texture != 0 {
+                gl::DeleteTextures(1, &self.depth_texture);
+            }
+
+            if self.stencil_render_buffer_owned && self.stencil_render_buffer != 0 {
+                gl::DeleteRenderbuffers(1, &self.stencil_render_buffer);
+            }
+        }
+
+        self.color_render_buffer = 0;
+        self.depth_render_buffer = 0;
+        self.depth_render_buffer_owned = true;
+        self.depth_texture = 0;
+        self.stencil_render_buffer = 0;
+        self.stencil_render_buffer_owned = true;
+        self.color_texture = texture;
+        self.color_texture_owned = owned;
+        self.color_texture_level = 0;
+        self.color_texture_storage = None;
+        self.color_format = format;
+        self.depth_format = 0;
+        self.stencil_format = 0;
+        self.size = size;
+        self.depth_stencil_size = size;
+        self.contents_defined.set(false);
+        self.multiview = Some(Multiview {
+            base_view: base_view,
+            num_views: num_views,
+            attach: unsafe { mem::transmute(attach) },
+        });
+
+        self.attach_renderbuffers_to_framebuffer()
+    }
+
+    /// The first view and the amount of views of a multiview color
+    /// texture, if any.
+    pub fn multiview_views(&self) -> Option<(GLint, GLsizei)> {
+        self.multiview.map(|multiview| (multiview.base_view, multiview.num_views))
+    }
+
+    /// Replaces the depth buffer with a texture of the given `format`.
+    /// Packed depth-stencil formats (`GL_DEPTH24_STENCIL8` and
+    /// `GL_DEPTH32F_STENCIL8`) are attached to `GL_DEPTH_STENCIL_ATTACHMENT`,
+    /// replacing the stencil buffer too.
+    ///
+    /// If `owned` is true the texture is deleted when the draw
+    /// buffer is dropped (or when it's replaced again).
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn set_depth_texture(&mut self, texture: GLuint, format: GLenum, owned: bool)
+        -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.ensure_allocated());
+
+        // Every attachment must have the same amount of samples
+        if self.samples > 0 {
+            return Err(GLContextError::InvalidOperation("Can't attach a single-sampled depth texture to a multisampled draw buffer"));
+        }
+
+        let packed = is_packed_depth_stencil_format(format);
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                        gl::DEPTH_ATTACHMENT,
+                                        gl::RENDERBUFFER,
+                                        0);
+
+            if self.depth_render_buffer_owned && self.depth_render_buffer != 0 {
+                gl::DeleteRenderbuffers(1, &self.depth_render_buffer);
+            }
+
+            if self.depth_texture_owned && self.depth_texture != 0 {
+                gl::DeleteTextures(1, &self.depth_texture);
+            }
+
+            if packed {
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                            gl::STENCIL_ATTACHMENT,
+                                            gl::RENDERBUFFER,
+                                            0);
+
+                if self.stencil_render_buffer_owned && self.stencil_render_buffer != 0 {
+                    gl::DeleteRenderbuffers(1, &self.stencil_render_buffer);
+                }
+
+                self.stencil_render_buffer = 0;
+                self.stencil_render_buffer_owned = true;
+                self.stencil_format = format;
+            } else if is_packed_depth_stencil_format(self.depth_format) {
+                // The previous texture was our stencil buffer
+                self.stencil_format = 0;
+            }
+        }
+
+        self.depth_render_buffer = 0;
+        self.depth_render_buffer_owned = true;
+        self.depth_texture = texture;
+        self.depth_texture_owned = owned;
+        self.depth_format = format;
+
+        self.attach_renderbuffers_to_framebuffer()
+    }
+
+    /// Issues an asynchronous read of the color buffer into `readback`.
+    /// The data will be available through `PersistentReadback::wait`.
+    /// Multisampled buffers are resolved first.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_into_persistent(&self, readback: &mut PersistentReadback)
+        -> Result<()> {
+        try!(self.check_allocated());
+
+        if readback.get_size() != self.size {
+            return Err(GLContextError::InvalidRequest("The readback buffer size doesn't match"));
+        }
+
+        let _guard = GLStateGuard::new();
+
+        let framebuffer = try!(self.read_framebuffer());
+
+        // NOTE: Persistent mapping requires GL 4.4, so there's always
+        // a separate read framebuffer and PACK_ROW_LENGTH
+        unsafe {
+            gl::BindFramebuffer(READ_FRAMEBUFFER, framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::PixelStorei(PACK_ROW_LENGTH, 0);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, readback.get_buffer());
+            gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                           gl::RGBA, gl::UNSIGNED_BYTE, 0 as *mut _);
+        }
+
+        try!(check_gl_error("glReadPixels"));
+
+        readback.insert_fence()
+    }
+
+    /// Reads the color buffer as RGBA8 (bottom row first) into the GL
+    /// buffer object `buffer`, starting at `offset` bytes, so the data
+    /// stays on the GPU. The buffer must already have storage for it.
+    ///
+    /// Requires GL 2.1 or GLES 3.0. The pixel pack buffer binding is
+    /// restored afterwards.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn read_pixels_to_buffer(&self, buffer: GLuint, offset: usize) -> Result<()> {
+        try!(self.check_allocated());
+
+        if !GLFeature::is_supported(GLFeature::PixelBufferObject) {
+            return Err(GLContextError::Unsupported("Reading into buffer objects requires GL 2.1 or GLES 3.0"));
+        }
+
+        if buffer == 0 {
+            return Err(GLContextError::InvalidRequest("Invalid buffer"));
+        }
+
+        let len = self.size.width as usize * self.size.height as usize * 4;
+
+        // NOTE: The guard restores the application's pixel pack buffer
+        let _guard = GLStateGuard::new();
+        let mut capacity: GLint = 0;
+
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
+            gl::GetBufferParameteriv(gl::PIXEL_PACK_BUFFER, BUFFER_SIZE, &mut capacity);
+        }
+
+        let fits = offset.checked_add(len).map_or(false, |end| end <= capacity as usize);
+        if !fits {
+            return Err(GLContextError::InvalidRequest("The buffer is too small"));
+        }
+
+        let framebuffer = try!(self.read_framebuffer());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, self.size.width, self.size.height,
+                           gl::RGBA, gl::UNSIGNED_BYTE, offset as *mut _);
+        }
+
+        check_gl_error("glReadPixels")
+    }
+
+    /// Names the framebuffer and its attachments for GPU debuggers (like
+    /// RenderDoc) with `glObjectLabel`: the framebuffer gets `label`,
+    /// and the attachments `label` followed by their role, like
+    /// "label (depth)".
+    ///
+    /// This is a no-op unless `GL_KHR_debug` (or GL 4.3 / GLES 3.2) is
+    /// available.
+    ///
+    /// Makes `context` current if needed.
+    pub fn set_label(&self, context: &GLContext, label: &str) -> Result<()> {
+        if !self.is_allocated() {
+            return Ok(());
+        }
+
+        try!(context.make_current());
+
+        let (is_gles, version) = get_gl_version();
+        let core = if is_gles { version >= 32 } else { version >= 43 };
+        if !core && !context.has_extension("GL_KHR_debug") {
+            return Ok(());
+        }
+
+        let set = |identifier: GLenum, name: GLuint, label: &str| {
+            if name == 0 {
+                return;
+            }
+
+            unsafe {
+                gl::ObjectLabel(identifier, name, label.len() as GLsizei,
+                                label.as_ptr() as *const _);
+            }
+        };
+
+        set(gl::FRAMEBUFFER, self.framebuffer, label);
+        set(gl::RENDERBUFFER, self.color_render_buffer, &format!("{} (color)", label));
+        set(gl::TEXTURE, self.color_texture, &format!("{} (color)", label));
+        set(gl::TEXTURE, self.depth_texture, &format!("{} (depth)", label));
+
+        if is_packed_depth_stencil_format(self.depth_format) {
+            set(gl::RENDERBUFFER, self.depth_render_buffer, &format!("{} (depth-stencil)", label));
+        } else {
+            set(gl::RENDERBUFFER, self.depth_render_buffer, &format!("{} (depth)", label));
+            set(gl::RENDERBUFFER, self.stencil_render_buffer, &format!("{} (stencil)", label));
+        }
+
+        for (i, &render_buffer) in self.extra_color_render_buffers.iter().enumerate() {
+            set(gl::RENDERBUFFER, render_buffer, &format!("{} (color {})", label, i + 1));
+        }
+
+        Ok(())
+    }
+
+    /// Creates a draw buffer for `context` rendering into the same
+    /// attachments as this one, for contexts of the same share group.
+    /// Render buffers and textures are shared, but framebuffers aren't,
+    /// so this creates a new framebuffer in `context`.
+    ///
+    /// The new draw buffer doesn't own the attachments, so this one
+    /// must outlive it, and it can't be resized.
+    ///
+    /// NOTE: This leaves `context` current.
+    pub fn rebind_in(&self, context: &GLContext) -> Result<DrawBuffer> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.check_allocated());
+
+        try!(context.make_current());
+
+        let mut draw_buffer = DrawBuffer::empty(self.size);
+        draw_buffer.depth_stencil_size = self.depth_stencil_size;
+        draw_buffer.attributes = self.attributes;
+        draw_buffer.owner = Some(deletion_queue_of(context));
+
+        draw_buffer.color_render_buffer = self.color_render_buffer;
+        draw_buffer.color_render_buffer_owned = false;
+        draw_buffer.color_texture = self.color_texture;
+        draw_buffer.color_texture_owned = false;
+        draw_buffer.color_texture_level = self.color_texture_level;
+        draw_buffer.color_format = self.color_format;
+        draw_buffer.extra_color_render_buffers = self.extra_color_render_buffers.clone();
+        draw_buffer.extra_color_render_buffers_owned = false;
+        draw_buffer.depth_render_buffer = self.depth_render_buffer;
+        draw_buffer.depth_render_buffer_owned = false;
+        draw_buffer.depth_texture = self.depth_texture;
+        draw_buffer.depth_texture_owned = false;
+        draw_buffer.depth_format = self.depth_format;
+        draw_buffer.stencil_render_buffer = self.stencil_render_buffer;
+        draw_buffer.stencil_render_buffer_owned = false;
+        draw_buffer.stencil_format = self.stencil_format;
+        draw_buffer.samples = self.samples;
+        draw_buffer.multiview = self.multiview;
+        draw_buffer.flip_y = self.flip_y;
+        draw_buffer.composite_op = self.composite_op;
+        draw_buffer.color_mask = self.color_mask;
+        draw_buffer.contents_defined.set(self.contents_defined.get());
+
+        let _guard = GLStateGuard::new();
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut draw_buffer.framebuffer);
+            debug_assert!(draw_buffer.framebuffer != 0);
+        }
+
+        try!(draw_buffer.attach_renderbuffers_to_framebuffer());
+
+        Ok(draw_buffer)
+    }
+
+    /// Detaches every attachment from the framebuffer, without deleting
+    /// the render buffers or textures, so the framebuffer can be reused
+    /// with other attachments. The draw buffer is incomplete until they
+    /// are attached again, with `reattach` or any of the `set_*` methods.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn detach_all(&mut self) -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.check_allocated());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+
+            if self.color_texture != 0 {
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0,
+                                         gl::TEXTURE_2D, 0, 0);
+            } else {
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0,
+                                            gl::RENDERBUFFER, 0);
+            }
+
+            for i in 0..self.extra_color_render_buffers.len() {
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                            gl::COLOR_ATTACHMENT0 + 1 + i as GLenum,
+                                            gl::RENDERBUFFER, 0);
+            }
+
+            if self.depth_texture != 0 {
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT,
+                                         gl::TEXTURE_2D, 0, 0);
+            } else {
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT,
+                                            gl::RENDERBUFFER, 0);
+            }
+
+            // NOTE: This also detaches packed depth-stencil textures
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::STENCIL_ATTACHMENT,
+                                        gl::RENDERBUFFER, 0);
+        }
+
+        self.detached = true;
+
+        check_gl_error("Error detaching the draw buffer attachments")
+    }
+
+    /// Attaches the draw buffer objects again after `detach_all`.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn reattach(&mut self) -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.check_allocated());
+        self.attach_renderbuffers_to_framebuffer()
+    }
+
+    /// Returns the GL object holding the color buffer, with a fence the
+    /// consumer must wait for. Multisampled buffers must be resolved
+    /// first (see `resolve_to_texture`).
+    ///
+    /// The object is still owned by the draw buffer.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn export_color_buffer(&self) -> Result<ExportedColorBuffer> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.check_allocated());
+
+        if self.samples > 0 {
+            return Err(GLContextError::InvalidOperation("Multisampled color buffers can't be exported"));
+        }
+
+        if self.multiview.is_some() {
+            return Err(GLContextError::InvalidOperation("Multiview color textures can't be exported"));
+        }
+
+        let (target, name) = if self.color_texture != 0 {
+            (gl::TEXTURE_2D, self.color_texture)
+        } else {
+            (gl::RENDERBUFFER, self.color_render_buffer)
+        };
+
+        let fence = if GLFeature::is_supported(GLFeature::FenceSync) {
+            Some(try!(Fence::insert()))
+        } else {
+            unsafe {
+                gl::Finish();
+            }
+            None
+        };
+
+        Ok(ExportedColorBuffer {
+            target: target,
+            name: name,
+            format: self.color_format,
+            size: self.size,
+            fence: fence,
+        })
+    }
+
+    /// Finishes the rendered frame for another thread, process or
+    /// context: flushes the pending commands and returns the color
+    /// object with a fence, like `export_color_buffer`.
+    ///
+    /// Multisampled draw buffers are resolved first, and the returned
+    /// object is then the (single sampled) render buffer they're
+    /// resolved into, which is reused by the next resolve, and replaced
+    /// on resize.
+    ///
+    /// The consumer must wait for the fence (see `ExportedColorBuffer`)
+    /// before reading the object, or it may see a partially rendered
+    /// frame, and must be done with it before the next frame is handed
+    /// off.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn prepare_for_handoff(&self) -> Result<Handoff> {
+        if self.samples == 0 {
+            return self.export_color_buffer();
+        }
+
+        try!(self.check_not_default_framebuffer());
+        try!(self.check_allocated());
+
+        let _guard = GLStateGuard::new();
+
+        try!(self.resolve());
+
+        // NOTE: `resolve` creates it if needed
+        let (_, render_buffer) = self.resolve_target.get().unwrap();
+
+        let mut format: GLint = 0;
+
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, render_buffer);
+            gl::GetRenderbufferParameteriv(gl::RENDERBUFFER, RENDERBUFFER_INTERNAL_FORMAT, &mut format);
+        }
+
+        let fence = if GLFeature::is_supported(GLFeature::FenceSync) {
+            Some(try!(Fence::insert()))
+        } else {
+            unsafe {
+                gl::Finish();
+            }
+            None
+        };
+
+        Ok(Handoff {
+            target: gl::RENDERBUFFER,
+            name: render_buffer,
+            format: format as GLenum,
+            size: self.size,
+            fence: fence,
+        })
+    }
+
+    /// Binds the color texture to the image `unit` with the given `access`
+    /// (`GL_READ_ONLY`, `GL_WRITE_ONLY` or `GL_READ_WRITE`), so compute
+    /// shaders can read or write it. The color attachment must be a
+    /// texture (see `set_color_texture`) with an image load/store
+    /// compatible format, like `GL_RGBA8` or `GL_RGBA16F`.
+    ///
+    /// Requires GL 4.2 or GLES 3.1.
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn bind_color_as_image(&self, unit: GLuint, access: GLenum) -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.check_allocated());
+
+        if !GLFeature::is_supported(GLFeature::ImageLoadStore) {
+            return Err(GLContextError::Unsupported("Image load/store is not supported"));
+        }
+
+        if self.color_texture == 0 {
+            return Err(GLContextError::InvalidOperation("The color attachment is not a texture"));
+        }
+
+        if !is_image_format(self.color_format) {
+            return Err(GLContextError::InvalidOperation("The color texture format can't be bound as an image"));
+        }
+
+        match access {
+            READ_ONLY | WRITE_ONLY | READ_WRITE => {},
+            _ => return Err(GLContextError::InvalidRequest("Invalid image access")),
+        }
+
+        let mut max_units = 0;
+        unsafe {
+            gl::GetIntegerv(MAX_IMAGE_UNITS, &mut max_units);
+        }
+
+        if unit >= max_units as GLuint {
+            return Err(GLContextError::InvalidRequest("The image unit is out of range"));
+        }
+
+        unsafe {
+            gl::BindImageTexture(unit, self.color_texture, self.color_texture_level,
+                                 gl::FALSE, 0, access, self.color_format);
+        }
+
+        check_gl_error("glBindImageTexture")
+    }
 
-    unsafe {
-        gl::GenRenderbuffers(1, &mut ret);
-        gl::BindRenderbuffer(gl::RENDERBUFFER, ret);
-        gl::RenderbufferStorage(gl::RENDERBUFFER, format, size.width, size.height);
+    /// Replaces the color render buffer with a user-supplied one
+    /// (for example one backed by an EGLImage).
+    ///
+    /// If `owned` is true the render buffer is deleted when the draw
+    /// buffer is dropped (or when it's replaced again).
+    ///
+    /// NOTE: Like `drop`, this assumes the associated context is current.
+    pub fn set_color_renderbuffer(&mut self, render_buffer: GLuint, owned: bool)
+        -> Result<()> {
+        try!(self.check_not_default_framebuffer());
+        try!(self.ensure_allocated());
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                        gl::COLOR_ATTACHMENT0,
+                                        gl::RENDERBUFFER,
+                                        0);
+
+            if self.color_render_buffer_owned && self.color_render_buffer != 0 {
+                gl::DeleteRenderbuffers(1, &self.color_render_buffer);
+            }
+
+            if self.color_texture_owned && self.color_texture != 0 {
+                gl::DeleteTextures(1, &self.color_texture);
+            }
+        }
+
+        self.color_texture = 0;
+        self.color_texture_storage = None;
+        self.multiview = None;
+        self.color_render_buffer = render_buffer;
+        self.color_render_buffer_owned = owned;
+
+        self.attach_renderbuffers_to_framebuffer()
     }
+}
 
-    ret
+// How many times `DrawBufferBuilder` retries an allocation that ran
+// out of memory, so a callback that never frees enough can't loop
+// forever.
+const MAX_OUT_OF_MEMORY_RETRIES: usize = 3;
+
+/// Creates draw buffers with options the constructors don't take,
+/// like retrying allocations that ran out of memory.
+pub struct DrawBufferBuilder<'a> {
+    context: &'a GLContext,
+    // Validated on creation, but reported by `build`
+    size: Result<Size2D<i32>>,
+    attributes: GLContextAttributes,
+    on_out_of_memory: Option<Box<FnMut() -> bool + 'a>>,
 }
 
-impl DrawBuffer {
-    pub fn new(context: &GLContext, size: Size2D<i32>)
-        -> Result<DrawBuffer, &'static str> {
+impl<'a> DrawBufferBuilder<'a> {
+    /// Starts with the context attributes, and without retries.
+    /// Sizes GL can't represent make `build` fail.
+    pub fn new<S: ToGLSize>(context: &'a GLContext, size: S) -> DrawBufferBuilder<'a> {
+        DrawBufferBuilder {
+            context: context,
+            size: size.to_gl_size(),
+            attributes: *context.borrow_attributes(),
+            on_out_of_memory: None,
+        }
+    }
 
-        let attrs = context.borrow_attributes();
-        let capabilities = context.borrow_capabilities();
+    pub fn attributes(mut self, attributes: GLContextAttributes) -> DrawBufferBuilder<'a> {
+        self.attributes = attributes;
+        self
+    }
+
+    /// Sets a callback to run when the allocation fails with
+    /// `GL_OUT_OF_MEMORY`, to free some memory (flushing a pool of
+    /// buffers, for example). If it returns true the allocation is
+    /// retried, up to a few times.
+    pub fn on_out_of_memory<F>(mut self, callback: F) -> DrawBufferBuilder<'a>
+        where F: FnMut() -> bool + 'a {
+        self.on_out_of_memory = Some(Box::new(callback));
+        self
+    }
+
+    pub fn build(mut self) -> Result<DrawBuffer> {
+        let size = try!(self.size);
+        let mut retries = 0;
+
+        loop {
+            let result = DrawBuffer::new_with_attributes(self.context, size, self.attributes);
+
+            let retry = match (&result, self.on_out_of_memory.as_mut()) {
+                (&Err(GLContextError::OutOfMemory(_)), Some(callback)) => {
+                    retries < MAX_OUT_OF_MEMORY_RETRIES && callback()
+                }
+                _ => false,
+            };
 
-        if attrs.antialias && capabilities.max_samples == 0 {
-            return Err("The given GLContext doesn't support requested antialising");
+            if !retry {
+                return result;
+            }
+
+            warn!("Out of memory allocating a draw buffer, retrying");
+            retries += 1;
         }
+    }
+}
 
-        let mut draw_buffer = DrawBuffer {
-            size: size,
-            framebuffer: 0,
-            color_render_buffer: 0,
-            stencil_render_buffer: 0,
-            depth_render_buffer: 0,
-            // samples: 0,
-        };
+/// The color attachment points used by a draw buffer with `count`
+/// color buffers, in order, as passed to `glDrawBuffers`.
+fn color_attachment_list(count: usize) -> Vec<GLenum> {
+    (0..count).map(|i| gl::COLOR_ATTACHMENT0 + i as GLenum).collect()
+}
 
-        try!(context.make_current());
+/// Returns the size of the mip `level` of a texture whose base level
+/// is `base_size`, or `None` if the texture can't have such a level.
+fn mip_level_size(base_size: &Size2D<i32>, level: GLint) -> Option<Size2D<i32>> {
+    if level < 0 || level >= 32 || base_size.width <= 0 || base_size.height <= 0 {
+        return None;
+    }
+
+    let largest = cmp::max(base_size.width, base_size.height);
+    if (largest >> level) == 0 {
+        return None;
+    }
+
+    Some(Size2D(cmp::max(1, base_size.width >> level),
+                cmp::max(1, base_size.height >> level)))
+}
+
+/// Applies a color mask, restoring the previous one when dropped.
+struct ColorMaskGuard {
+    previous: [GLboolean; 4],
+}
+
+impl ColorMaskGuard {
+    fn new(mask: [bool; 4]) -> ColorMaskGuard {
+        let mut previous = [gl::TRUE; 4];
 
-        try!(draw_buffer.init(&attrs));
+        let as_gl = |value: bool| if value { gl::TRUE } else { gl::FALSE };
 
         unsafe {
-            debug_assert!(gl::GetError() == gl::NO_ERROR);
+            gl::GetBooleanv(gl::COLOR_WRITEMASK, previous.as_mut_ptr());
+            gl::ColorMask(as_gl(mask[0]), as_gl(mask[1]), as_gl(mask[2]), as_gl(mask[3]));
         }
 
-        Ok(draw_buffer)
+        ColorMaskGuard {
+            previous: previous,
+        }
+    }
+}
+
+impl Drop for ColorMaskGuard {
+    fn drop(&mut self) {
+        unsafe {
+            gl::ColorMask(self.previous[0], self.previous[1], self.previous[2], self.previous[3]);
+        }
+    }
+}
+
+/// Restores the size of a draw buffer if `with_temporary_size`'s
+/// closure panics.
+struct RestoreSizeGuard<'a> {
+    draw_buffer: &'a mut DrawBuffer,
+    size: Size2D<i32>,
+    depth_stencil_size: Size2D<i32>,
+    restored: bool,
+}
+
+impl<'a> RestoreSizeGuard<'a> {
+    fn restore(&mut self) -> Result<()> {
+        try!(self.draw_buffer.resize(self.size));
+
+        if self.depth_stencil_size != self.size {
+            try!(self.draw_buffer.resize_depth_stencil(self.depth_stencil_size));
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> Drop for RestoreSizeGuard<'a> {
+    fn drop(&mut self) {
+        if !self.restored {
+            let _ = self.restore();
+        }
     }
 }
 
@@ -70,67 +2994,373 @@ impl DrawBuffer {
 // when drop is called. I know this is an important constraint.
 // Right now there are no problems, if not, consider using a pointer to a
 // parent with Rc<GLContext> and call make_current()
+//
+// Careful callers can use `DrawBuffer::destroy` instead, in which case
+// this is a no-op.
+// If the owning context isn't known to be current, the objects are
+// queued for deletion instead (see `GLContext::flush_pending_deletions`).
+// If it was already dropped, nothing is deleted, since the objects were
+// destroyed with it, and whatever context is current now isn't ours.
 impl Drop for DrawBuffer {
     fn drop(&mut self) {
-        unsafe {
-            gl::DeleteFramebuffers(1, &mut self.framebuffer);
+        let mut objects = PendingDeletions::new();
+        self.take_objects(&mut objects);
 
-            let mut render_buffers = [
-                self.color_render_buffer,
-                self.stencil_render_buffer,
-                self.depth_render_buffer
-            ];
-
-            gl::DeleteRenderbuffers(3, render_buffers.as_mut_ptr());
+        match self.owner {
+            Some((_, ref queue)) if queue.borrow().is_context_destroyed() => {
+                warn!("Dropping a draw buffer after its context, its objects were destroyed with it");
+            }
+            Some((id, ref queue)) if id != current_context_id() => {
+                warn!("Dropping a draw buffer while its context isn't current, deferring deletion");
+                queue.borrow_mut().append(&mut objects);
+            }
+            _ => objects.flush(),
         }
     }
 }
 
 trait DrawBufferHelpers {
-    fn init(&mut self, attrs: &GLContextAttributes)   -> Result<(), &'static str>;
-    fn attach_renderbuffers_to_framebuffer(&mut self) -> Result<(), &'static str>;
+    fn empty(size: Size2D<i32>) -> Self;
+    fn check_capabilities(attrs: &GLContextAttributes,
+                          capabilities: &GLContextCapabilities) -> GLsizei;
+    fn allocate(size: Size2D<i32>,
+                attrs: &GLContextAttributes,
+                capabilities: &GLContextCapabilities,
+                samples: GLsizei) -> Result<Self>;
+    fn init(&mut self, attrs: &GLContextAttributes, capabilities: &GLContextCapabilities) -> Result<()>;
+    fn create_color_buffer(&mut self,
+                           attrs: &GLContextAttributes,
+                           capabilities: &GLContextCapabilities) -> Result<()>;
+    fn create_depth_stencil_buffers(&mut self,
+                                    attrs: &GLContextAttributes,
+                                    capabilities: &GLContextCapabilities) -> Result<()>;
+    fn attach_renderbuffers_to_framebuffer(&mut self) -> Result<()>;
+    fn check_framebuffer_status(&self) -> Result<()>;
+    fn set_default_framebuffer_parameters(&self) -> Result<()>;
+    fn check_allocated(&self) -> Result<()>;
+    fn check_not_default_framebuffer(&self) -> Result<()>;
+    fn check_sample_counts(&self) -> Result<()>;
+    fn resolve(&self) -> Result<GLuint>;
+    fn read_framebuffer(&self) -> Result<GLuint>;
+    fn add_depth_for_stencil(&mut self, capabilities: &GLContextCapabilities) -> Result<()>;
+    fn take_objects(&mut self, objects: &mut PendingDeletions);
+    fn delete_objects(&mut self);
 }
 
 impl DrawBufferHelpers for DrawBuffer {
-    fn init(&mut self, attrs: &GLContextAttributes) -> Result<(), &'static str> {
+    fn empty(size: Size2D<i32>) -> DrawBuffer {
+        DrawBuffer {
+            size: size,
+            depth_stencil_size: size,
+            framebuffer: 0,
+            framebuffer_owned: true,
+            stencil_render_buffer_owned: true,
+            depth_render_buffer_owned: true,
+            color_render_buffer: 0,
+            color_render_buffer_owned: true,
+            color_texture: 0,
+            color_texture_owned: true,
+            color_texture_level: 0,
+            color_texture_storage: None,
+            color_format: 0,
+            extra_color_render_buffers: vec![],
+            extra_color_render_buffers_owned: true,
+            depth_texture: 0,
+            depth_texture_owned: true,
+            depth_format: 0,
+            stencil_render_buffer: 0,
+            depth_render_buffer: 0,
+            stencil_format: 0,
+            flip_y: false,
+            samples: 0,
+            attributes: GLContextAttributes::any(),
+            contents_defined: Cell::new(false),
+            composite_op: CompositeOp::Copy,
+            lazy: None,
+            pending_clear: Cell::new(None),
+            detached: false,
+            color_mask: [true; 4],
+            multiview: None,
+            resolve_target: Cell::new(None),
+            resolution_log: vec![],
+            is_default_framebuffer: false,
+            owner: None,
+        }
+    }
+
+    // Returns the amount of samples to use.
+    // NOTE: Assumes the request passed `validate_request`
+    fn check_capabilities(attrs: &GLContextAttributes,
+                          capabilities: &GLContextCapabilities) -> GLsizei {
+        if let Some(quality) = attrs.antialias_quality {
+            return quality.samples(capabilities.max_samples);
+        }
+
+        if !attrs.antialias {
+            return 0;
+        }
+
+        if attrs.samples <= capabilities.max_samples {
+            return attrs.samples;
+        }
+
+        // Only possible with `AntialiasPolicy::BestEffort`
+        warn!("Requested {} samples, using {}", attrs.samples, capabilities.max_samples);
+        capabilities.max_samples
+    }
+
+    // NOTE: Assumes the context is current
+    fn allocate(size: Size2D<i32>,
+                attrs: &GLContextAttributes,
+                capabilities: &GLContextCapabilities,
+                samples: GLsizei)
+        -> Result<DrawBuffer> {
+        let mut draw_buffer = DrawBuffer::empty(size);
+        draw_buffer.samples = samples;
+        draw_buffer.attributes = *attrs;
+
+        try!(draw_buffer.init(attrs, capabilities));
+
+        try!(check_gl_error("Error creating the draw buffer"));
+
+        debug!("Created a {}x{} draw buffer (color format {:#x}, {} samples)",
+               size.width, size.height, draw_buffer.color_format, samples);
+
+        Ok(draw_buffer)
+    }
+
+    fn init(&mut self, attrs: &GLContextAttributes, capabilities: &GLContextCapabilities)
+        -> Result<()> {
+        self.resolution_log.clear();
+
+        if let Some(quality) = attrs.antialias_quality {
+            self.resolution_log.push(format!("samples: {:?} quality, using {}", quality, self.samples));
+        } else if attrs.antialias {
+            self.resolution_log.push(format!("samples: requested {}, using {}", attrs.samples, self.samples));
+        }
+
+        // We only create single-sampled color textures (multisample ones
+        // need GL 3.2 / GLES 3.1 and can't be sampled normally anyway), so
+        // fail clearly instead of with an incomplete framebuffer
+        if self.samples > 0 && attrs.color_texture && !attrs.no_attachments {
+            return Err(GLContextError::Unsupported("Antialiasing is not supported with color_texture, use a render buffer color attachment and resolve it into a texture instead (see DrawBuffer::resolve_to_texture)"));
+        }
+
+        if attrs.no_attachments {
+            self.resolution_log.push("no attachments, using default framebuffer parameters".to_string());
+            unsafe {
+                gl::GenFramebuffers(1, &mut self.framebuffer);
+                debug_assert!(self.framebuffer != 0);
+            }
+
+            return self.set_default_framebuffer_parameters();
+        }
+
+        try!(self.create_color_buffer(attrs, capabilities));
+
+        // Implementations may round the amount of samples up, and every
+        // attachment must match the color one, or the framebuffer is
+        // incomplete (GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE)
+        if self.samples > 0 && self.color_render_buffer != 0 {
+            let samples = render_buffer_samples(self.color_render_buffer);
+            if samples != self.samples {
+                debug!("Requested {} samples, the implementation allocated {}", self.samples, samples);
+                self.resolution_log.push(format!("samples: the implementation rounded {} up to {}",
+                                                 self.samples, samples));
+                self.samples = samples;
+
+                for &render_buffer in self.extra_color_render_buffers.iter() {
+                    resize_render_buffer(render_buffer, self.color_format, &self.size, samples);
+                }
+            }
+        }
+
+        // After this we check if we need stencil and depth buffers
+        try!(self.create_depth_stencil_buffers(attrs, capabilities));
+        try!(self.check_sample_counts());
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut self.framebuffer);
+            debug_assert!(self.framebuffer != 0);
+        }
+
+        // Finally we attach them to the framebuffer
+        let result = self.attach_renderbuffers_to_framebuffer();
+
+        if result.is_err() && attrs.stencil && !attrs.depth && attrs.stencil_only_workaround {
+            let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+            if status == gl::FRAMEBUFFER_UNSUPPORTED {
+                warn!("Stencil-only framebuffers are unsupported, adding a depth buffer");
+                try!(self.add_depth_for_stencil(capabilities));
+                let entry = format!("depth: {} (stencil-only framebuffers unsupported)",
+                                    format_name(self.depth_format));
+                self.resolution_log.push(entry);
+                return self.attach_renderbuffers_to_framebuffer();
+            }
+        }
+
+        result
+    }
+
+    // Works around drivers that don't support stencil without depth, by
+    // switching to a packed depth-stencil buffer, or adding a depth one.
+    fn add_depth_for_stencil(&mut self, capabilities: &GLContextCapabilities)
+        -> Result<()> {
+        let size = self.depth_stencil_size;
+
+        if capabilities.extensions.contains(EXT_PACKED_DEPTH_STENCIL) {
+            if let Some(render_buffer) = try!(try_create_render_buffer(DEPTH24_STENCIL8, &size, self.samples)) {
+                unsafe {
+                    gl::DeleteRenderbuffers(1, &self.stencil_render_buffer);
+                }
+
+                self.stencil_render_buffer = 0;
+                self.depth_render_buffer = render_buffer;
+                self.depth_format = DEPTH24_STENCIL8;
+                self.stencil_format = DEPTH24_STENCIL8;
+                return Ok(());
+            }
+        }
+
+        match try!(try_create_render_buffer(gl::DEPTH_COMPONENT16, &size, self.samples)) {
+            Some(render_buffer) => {
+                self.depth_render_buffer = render_buffer;
+                self.depth_format = gl::DEPTH_COMPONENT16;
+                Ok(())
+            }
+            None => Err(GLContextError::GLError("Couldn't create the depth buffer for the stencil-only workaround")),
+        }
+    }
+
+    fn create_color_buffer(&mut self,
+                           attrs: &GLContextAttributes,
+                           capabilities: &GLContextCapabilities) -> Result<()> {
         // The color render buffer is always there
-        // TODO(ecoal95): Allow RGBA8 and RGB8 (via conditional detection)
+        // TODO(ecoal95): Allow RGB8 (via conditional detection)
         // FIXME(ecoal95): We can't depend on gl::RGB4 (not in GLES) and neither in
         //   gl::RGB565 (not in OGL 3), so assume there's always alpha channel
-        // if attrs.alpha {
-            self.color_render_buffer = create_render_buffer(gl::RGBA4, &self.size);
-        // } else {
-        //    self.color_render_buffer = create_render_buffer(gl::RGB4, &self.size);
-        // }
-        debug_assert!(self.color_render_buffer != 0);
+        //
+        // We prefer RGBA8, and fall back to RGBA4 (which is always supported)
+        // only if it satisfies the requested amount of alpha bits.
+        let min_alpha_bits = if attrs.alpha { attrs.min_alpha_bits } else { 0 };
+
+        let bgra = attrs.bgra && capabilities.extensions.contains(EXT_BGRA8_FORMAT);
+        if attrs.bgra && !bgra {
+            self.resolution_log.push("color: BGRA8 unsupported".to_string());
+        }
+
+        if attrs.color_texture {
+            let format = if bgra { BGRA8_EXT } else { RGBA8 };
+            let (texture, storage) = create_color_texture(&self.size, format, &attrs.color_texture_parameters);
+            self.color_texture = texture;
+            self.color_texture_storage = Some(storage);
+            self.color_format = format;
+            self.resolution_log.push(format!("color: {} texture ({:?} storage)",
+                                             if bgra { "BGRA8" } else { "RGBA8" }, storage));
+        } else if let Some(render_buffer) = if bgra {
+            try!(try_create_render_buffer(BGRA8_EXT, &self.size, self.samples))
+        } else {
+            None
+        } {
+            self.color_render_buffer = render_buffer;
+            self.color_format = BGRA8_EXT;
+            self.resolution_log.push("color: BGRA8".to_string());
+        } else if let Some(render_buffer) = try!(try_create_render_buffer(RGBA8, &self.size, self.samples)) {
+            self.color_render_buffer = render_buffer;
+            self.color_format = RGBA8;
+            self.resolution_log.push("color: RGBA8".to_string());
+        } else if min_alpha_bits <= 4 {
+            warn!("RGBA8 color buffers are not supported, falling back to RGBA4");
+            self.color_render_buffer = create_render_buffer(gl::RGBA4, &self.size, self.samples);
+            self.color_format = gl::RGBA4;
+            self.resolution_log.push("color: RGBA4 (RGBA8 unsupported)".to_string());
+        } else if min_alpha_bits <= 8 {
+            return Err(GLContextError::Unsupported("RGBA8 is not supported, and RGBA4 only has 4 alpha bits"));
+        } else {
+            return Err(GLContextError::Unsupported("No supported color format has more than 8 alpha bits"));
+        }
+        debug_assert!(self.color_render_buffer != 0 || self.color_texture != 0);
+
+        for _ in 1..attrs.color_attachments {
+            let render_buffer = create_render_buffer(self.color_format, &self.size, self.samples);
+            self.extra_color_render_buffers.push(render_buffer);
+        }
+
+        Ok(())
+    }
+
+    fn create_depth_stencil_buffers(&mut self,
+                                    attrs: &GLContextAttributes,
+                                    capabilities: &GLContextCapabilities) -> Result<()> {
+        // Packed depth-stencil is core in GL 3.0 and GLES 3.0, but GLES2
+        // needs GL_OES_packed_depth_stencil. In any case it only gives us
+        // 8 stencil bits.
+        if attrs.depth && attrs.stencil && attrs.stencil_bits <= 8 &&
+           capabilities.extensions.contains(EXT_PACKED_DEPTH_STENCIL) {
+            if let Some(render_buffer) = try!(try_create_render_buffer(DEPTH24_STENCIL8, &self.size, self.samples)) {
+                self.depth_render_buffer = render_buffer;
+                self.depth_format = DEPTH24_STENCIL8;
+                self.stencil_format = DEPTH24_STENCIL8;
+                self.resolution_log.push("depth and stencil: DEPTH24_STENCIL8".to_string());
+                return Ok(());
+            }
+
+            warn!("Couldn't create a packed depth-stencil buffer, using separate buffers");
+            self.resolution_log.push("depth and stencil: separate buffers (DEPTH24_STENCIL8 unsupported)".to_string());
+        } else if attrs.depth && attrs.stencil {
+            self.resolution_log.push("depth and stencil: separate buffers (no packed depth-stencil)".to_string());
+        }
 
-        // After this we check if we need stencil and depth buffers
         if attrs.depth {
-            self.depth_render_buffer = create_render_buffer(gl::DEPTH_COMPONENT16, &self.size);
+            self.depth_render_buffer = create_render_buffer(gl::DEPTH_COMPONENT16, &self.size, self.samples);
+            self.depth_format = gl::DEPTH_COMPONENT16;
             debug_assert!(self.depth_render_buffer != 0);
+            self.resolution_log.push("depth: DEPTH_COMPONENT16".to_string());
         }
 
         if attrs.stencil {
-            self.stencil_render_buffer = create_render_buffer(gl::STENCIL_INDEX8, &self.size);
-            debug_assert!(self.stencil_render_buffer != 0);
-        }
+            for &format in stencil_formats_for(attrs.stencil_bits).iter() {
+                if let Some(render_buffer) = try!(try_create_render_buffer(format, &self.size, self.samples)) {
+                    self.stencil_render_buffer = render_buffer;
+                    self.stencil_format = format;
+                    self.resolution_log.push(format!("stencil: requested {} bits, using {}",
+                                                     attrs.stencil_bits, format_name(format)));
+                    break;
+                }
 
-        unsafe {
-            gl::GenFramebuffers(1, &mut self.framebuffer);
-            debug_assert!(self.framebuffer != 0);
+                debug!("Stencil format {:#x} is not supported", format);
+            }
+
+            if self.stencil_render_buffer == 0 {
+                return Err(GLContextError::GLError("Couldn't create the stencil buffer"));
+            }
         }
 
-        // Finally we attach them to the framebuffer
-        self.attach_renderbuffers_to_framebuffer()
+        Ok(())
     }
 
-    fn attach_renderbuffers_to_framebuffer(&mut self) -> Result<(), &'static str> {
+    fn attach_renderbuffers_to_framebuffer(&mut self) -> Result<()> {
+        self.detached = false;
+
         unsafe {
             gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
             // NOTE: The assertion fails if the framebuffer is not bound
             debug_assert!(gl::IsFramebuffer(self.framebuffer) == gl::TRUE);
 
-            if self.color_render_buffer != 0 {
+            if let Some(multiview) = self.multiview {
+                (multiview.attach)(gl::FRAMEBUFFER,
+                                   gl::COLOR_ATTACHMENT0,
+                                   self.color_texture,
+                                   self.color_texture_level,
+                                   multiview.base_view,
+                                   multiview.num_views);
+            } else if self.color_texture != 0 {
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER,
+                                         gl::COLOR_ATTACHMENT0,
+                                         gl::TEXTURE_2D,
+                                         self.color_texture,
+                                         self.color_texture_level);
+            } else if self.color_render_buffer != 0 {
                 // debug_assert!(gl::IsRenderbuffer(self.color_render_buffer) == gl::TRUE);
                 gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
                                             gl::COLOR_ATTACHMENT0,
@@ -138,12 +3368,52 @@ impl DrawBufferHelpers for DrawBuffer {
                                             self.color_render_buffer);
             }
 
-            if self.depth_render_buffer != 0 {
+            let draw_buffers = color_attachment_list(1 + self.extra_color_render_buffers.len());
+
+            for (&attachment, &render_buffer) in draw_buffers[1..].iter().zip(self.extra_color_render_buffers.iter()) {
+                gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                            attachment,
+                                            gl::RENDERBUFFER,
+                                            render_buffer);
+            }
+
+            // Set the draw and read buffers explicitly where they exist
+            // (GL and GLES 3), so we don't depend on their defaults (or
+            // on whatever the previous attachments needed). GLES2 always
+            // uses COLOR_ATTACHMENT0.
+            let (_, version) = get_gl_version();
+            if version >= 30 {
+                gl::DrawBuffers(draw_buffers.len() as GLsizei, draw_buffers.as_ptr());
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            }
+
+            if self.depth_texture != 0 {
+                let attachment = if is_packed_depth_stencil_format(self.depth_format) {
+                    DEPTH_STENCIL_ATTACHMENT
+                } else {
+                    gl::DEPTH_ATTACHMENT
+                };
+
+                gl::FramebufferTexture2D(gl::FRAMEBUFFER,
+                                         attachment,
+                                         gl::TEXTURE_2D,
+                                         self.depth_texture,
+                                         0);
+            } else if self.depth_render_buffer != 0 {
                 // debug_assert!(gl::IsRenderbuffer(self.depth_render_buffer) == gl::TRUE);
                 gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
                                             gl::DEPTH_ATTACHMENT,
                                             gl::RENDERBUFFER,
                                             self.depth_render_buffer);
+
+                // NOTE: GLES2 doesn't have GL_DEPTH_STENCIL_ATTACHMENT,
+                // so we attach packed buffers to both attachment points
+                if is_packed_depth_stencil_format(self.depth_format) {
+                    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER,
+                                                gl::STENCIL_ATTACHMENT,
+                                                gl::RENDERBUFFER,
+                                                self.depth_render_buffer);
+                }
             }
 
             if self.stencil_render_buffer != 0 {
@@ -155,6 +3425,276 @@ impl DrawBufferHelpers for DrawBuffer {
             }
         }
 
+        self.check_framebuffer_status()
+    }
+
+    // NOTE: Assumes the framebuffer is bound
+    fn check_framebuffer_status(&self) -> Result<()> {
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            error!("{} ({:#x})", describe_framebuffer_status(status), status);
+            return Err(GLContextError::IncompleteFramebuffer(status));
+        }
+
+        Ok(())
+    }
+
+    fn check_allocated(&self) -> Result<()> {
+        if !self.is_allocated() {
+            return Err(GLContextError::InvalidOperation("The draw buffer isn't allocated yet (see DrawBuffer::bind)"));
+        }
+
+        Ok(())
+    }
+
+    // Resolves the color buffer into `resolve_target`, creating it if
+    // needed, and returns its framebuffer.
+    //
+    // The target is RGBA8, except for float and integer color buffers,
+    // which keep their format so their values survive the resolve.
+    // NOTE: Assumes the context is current, and that the caller
+    // restores the framebuffer bindings
+    fn resolve(&self) -> Result<GLuint> {
+        let framebuffer = match self.resolve_target.get() {
+            Some((framebuffer, _)) => framebuffer,
+            None => {
+                let format = if is_float_format(self.color_format) || is_integer_format(self.color_format) {
+                    self.color_format
+                } else {
+                    RGBA8
+                };
+                let target = try!(create_blit_target_with_format(&self.size, format));
+                self.resolve_target.set(Some(target));
+                target.0
+            }
+        };
+
+        try!(copy_color(self.framebuffer, &self.size, self.samples,
+                        framebuffer, &self.size, gl::NEAREST, CompositeOp::Copy));
+
+        Ok(framebuffer)
+    }
+
+    // Returns the framebuffer the color buffer can be read from: the
+    // draw buffer's own, or `resolve_target` for multisampled ones,
+    // which can't be read directly.
+    // NOTE: Same assumptions as `resolve`
+    fn read_framebuffer(&self) -> Result<GLuint> {
+        if self.samples > 0 {
+            self.resolve()
+        } else {
+            Ok(self.framebuffer)
+        }
+    }
+
+    // Checks that the depth and stencil buffers got the same amount of
+    // samples as the color one. Some drivers round the amount up
+    // differently per format, and report it only as an incomplete
+    // framebuffer otherwise.
+    fn check_sample_counts(&self) -> Result<()> {
+        if self.samples == 0 {
+            return Ok(());
+        }
+
+        let counts: Vec<GLsizei> = [self.depth_render_buffer, self.stencil_render_buffer]
+            .iter()
+            .filter(|&&render_buffer| render_buffer != 0)
+            .map(|&render_buffer| render_buffer_samples(render_buffer))
+            .collect();
+
+        if !sample_counts_match(self.samples, &counts) {
+            error!("The depth or stencil buffer has a different amount of samples than the color buffer");
+            return Err(GLContextError::IncompleteFramebuffer(FRAMEBUFFER_INCOMPLETE_MULTISAMPLE));
+        }
+
+        Ok(())
+    }
+
+    fn check_not_default_framebuffer(&self) -> Result<()> {
+        if self.is_default_framebuffer {
+            return Err(GLContextError::InvalidOperation("The default framebuffer attachments can't be accessed or replaced"));
+        }
+
         Ok(())
     }
+
+    // For framebuffers without attachments, which take their size
+    // and samples from these.
+    fn set_default_framebuffer_parameters(&self) -> Result<()> {
+        let size = storage_size(&self.size);
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+            gl::FramebufferParameteri(gl::FRAMEBUFFER, FRAMEBUFFER_DEFAULT_WIDTH, size.width);
+            gl::FramebufferParameteri(gl::FRAMEBUFFER, FRAMEBUFFER_DEFAULT_HEIGHT, size.height);
+            gl::FramebufferParameteri(gl::FRAMEBUFFER, FRAMEBUFFER_DEFAULT_SAMPLES, self.samples);
+        }
+
+        self.check_framebuffer_status()
+    }
+
+    // Moves every owned object to `objects`, and resets the handles
+    // so calling this twice is harmless.
+    fn take_objects(&mut self, objects: &mut PendingDeletions) {
+        if self.framebuffer_owned && self.framebuffer != 0 {
+            objects.framebuffers.push(self.framebuffer);
+        }
+
+        if self.stencil_render_buffer_owned && self.stencil_render_buffer != 0 {
+            objects.render_buffers.push(self.stencil_render_buffer);
+        }
+
+        if self.depth_render_buffer_owned && self.depth_render_buffer != 0 {
+            objects.render_buffers.push(self.depth_render_buffer);
+        }
+
+        if self.color_render_buffer_owned && self.color_render_buffer != 0 {
+            objects.render_buffers.push(self.color_render_buffer);
+        }
+
+        if let Some((framebuffer, render_buffer)) = self.resolve_target.get() {
+            objects.framebuffers.push(framebuffer);
+            objects.render_buffers.push(render_buffer);
+            self.resolve_target.set(None);
+        }
+
+        if self.extra_color_render_buffers_owned {
+            objects.render_buffers.extend(self.extra_color_render_buffers.drain(..));
+        } else {
+            self.extra_color_render_buffers.clear();
+        }
+
+        if self.color_texture_owned && self.color_texture != 0 {
+            objects.textures.push(self.color_texture);
+        }
+
+        if self.depth_texture_owned && self.depth_texture != 0 {
+            objects.textures.push(self.depth_texture);
+        }
+
+        self.framebuffer = 0;
+        self.color_texture = 0;
+        self.depth_texture = 0;
+        self.stencil_render_buffer = 0;
+        self.depth_render_buffer = 0;
+        self.color_render_buffer = 0;
+    }
+
+    // Deletes every owned object right away.
+    // NOTE: Assumes the context is current
+    fn delete_objects(&mut self) {
+        let mut objects = PendingDeletions::new();
+        self.take_objects(&mut objects);
+        objects.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Size2D;
+    use gleam::gl;
+
+    use AntialiasPolicy;
+    use AntialiasQuality;
+    use GLContextAttributes;
+    use GLContextCapabilities;
+    use GLExtensions;
+    use super::{DrawBuffer, DrawBufferHelpers};
+    use super::{color_attachment_list, mip_level_size, sample_counts_match, storage_size};
+
+    fn capabilities(max_samples: i32) -> GLContextCapabilities {
+        GLContextCapabilities {
+            max_samples: max_samples,
+            max_renderbuffer_size: 4096,
+            max_color_attachments: 1,
+            max_draw_buffers: 1,
+            extensions: GLExtensions::empty(),
+            gl_version: (false, 45),
+        }
+    }
+
+    #[test]
+    fn storage_is_never_empty() {
+        assert_eq!(storage_size(&Size2D(0, 0)), Size2D(1, 1));
+        assert_eq!(storage_size(&Size2D(0, 32)), Size2D(1, 32));
+        assert_eq!(storage_size(&Size2D(32, 0)), Size2D(32, 1));
+        assert_eq!(storage_size(&Size2D(-5, -1)), Size2D(1, 1));
+        assert_eq!(storage_size(&Size2D(64, 48)), Size2D(64, 48));
+    }
+
+    #[test]
+    fn mip_levels_clamp_to_one() {
+        let base = Size2D(16, 4);
+
+        assert_eq!(mip_level_size(&base, 0), Some(Size2D(16, 4)));
+        assert_eq!(mip_level_size(&base, 1), Some(Size2D(8, 2)));
+        assert_eq!(mip_level_size(&base, 2), Some(Size2D(4, 1)));
+        assert_eq!(mip_level_size(&base, 3), Some(Size2D(2, 1)));
+        assert_eq!(mip_level_size(&base, 4), Some(Size2D(1, 1)));
+        assert_eq!(mip_level_size(&Size2D(1, 1), 0), Some(Size2D(1, 1)));
+    }
+
+    #[test]
+    fn no_mip_levels_past_the_last() {
+        assert_eq!(mip_level_size(&Size2D(16, 4), 5), None);
+        assert_eq!(mip_level_size(&Size2D(1, 1), 1), None);
+        assert_eq!(mip_level_size(&Size2D(16, 16), -1), None);
+        assert_eq!(mip_level_size(&Size2D(16, 16), 32), None);
+        assert_eq!(mip_level_size(&Size2D(0, 16), 0), None);
+        assert_eq!(mip_level_size(&Size2D(16, -1), 0), None);
+    }
+
+    #[test]
+    fn depth_and_stencil_share_the_color_sample_count() {
+        let mut attrs = GLContextAttributes::default();
+        attrs.depth = true;
+        attrs.stencil = true;
+        attrs.antialias = true;
+        attrs.samples = 4;
+
+        // A single count is resolved for every attachment
+        assert_eq!(DrawBuffer::check_capabilities(&attrs, &capabilities(8)), 4);
+
+        attrs.samples = 16;
+        attrs.antialias_policy = AntialiasPolicy::BestEffort;
+        assert_eq!(DrawBuffer::check_capabilities(&attrs, &capabilities(8)), 8);
+
+        attrs.antialias = false;
+        assert_eq!(DrawBuffer::check_capabilities(&attrs, &capabilities(8)), 0);
+    }
+
+    #[test]
+    fn antialias_quality_is_clamped() {
+        let mut attrs = GLContextAttributes::default();
+        attrs.antialias_quality = Some(AntialiasQuality::High);
+
+        assert_eq!(DrawBuffer::check_capabilities(&attrs, &capabilities(32)), 8);
+        assert_eq!(DrawBuffer::check_capabilities(&attrs, &capabilities(4)), 4);
+
+        attrs.antialias_quality = Some(AntialiasQuality::Low);
+        assert_eq!(DrawBuffer::check_capabilities(&attrs, &capabilities(32)), 2);
+        assert_eq!(DrawBuffer::check_capabilities(&attrs, &capabilities(0)), 0);
+
+        attrs.antialias_quality = Some(AntialiasQuality::None);
+        assert_eq!(DrawBuffer::check_capabilities(&attrs, &capabilities(32)), 0);
+    }
+
+    #[test]
+    fn mismatched_sample_counts_are_detected() {
+        assert!(sample_counts_match(4, &[]));
+        assert!(sample_counts_match(4, &[4]));
+        assert!(sample_counts_match(4, &[4, 4]));
+        // A driver rounding the depth buffer up differently
+        assert!(!sample_counts_match(4, &[8, 4]));
+        assert!(!sample_counts_match(4, &[4, 0]));
+    }
+
+    #[test]
+    fn draw_buffers_list_every_color_attachment() {
+        assert_eq!(color_attachment_list(1), vec![gl::COLOR_ATTACHMENT0]);
+        assert_eq!(color_attachment_list(3), vec![gl::COLOR_ATTACHMENT0,
+                                                  gl::COLOR_ATTACHMENT0 + 1,
+                                                  gl::COLOR_ATTACHMENT0 + 2]);
+    }
 }
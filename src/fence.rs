@@ -0,0 +1,84 @@
+use gleam::gl;
+use gleam::gl::types::{GLenum, GLbitfield, GLsync};
+
+use GLFeature;
+use GLContextError;
+use Result;
+
+// NOTE: Not exposed by the GLES2 bindings
+const SYNC_GPU_COMMANDS_COMPLETE: GLenum = 0x9117;
+const SYNC_FLUSH_COMMANDS_BIT: GLbitfield = 0x0001;
+const ALREADY_SIGNALED: GLenum = 0x911A;
+const TIMEOUT_EXPIRED: GLenum = 0x911B;
+const CONDITION_SATISFIED: GLenum = 0x911C;
+const TIMEOUT_IGNORED: u64 = 0xFFFFFFFFFFFFFFFF;
+
+/// A GL sync object signaled once the commands issued before it
+/// complete on the GPU.
+///
+/// Requires GL 3.2 or GLES 3.0.
+///
+/// NOTE: As with `DrawBuffer`, a context of the share group it was
+/// created in must be current when it's dropped.
+pub struct Fence {
+    sync: GLsync,
+}
+
+impl Fence {
+    /// Inserts a fence after the commands issued so far, and flushes
+    /// them, so other contexts waiting for it don't wait forever.
+    pub fn insert() -> Result<Fence> {
+        if !GLFeature::is_supported(GLFeature::FenceSync) {
+            return Err(GLContextError::Unsupported("Sync objects require GL 3.2 or GLES 3.0"));
+        }
+
+        let sync = unsafe {
+            let sync = gl::FenceSync(SYNC_GPU_COMMANDS_COMPLETE, 0);
+            gl::Flush();
+            sync
+        };
+
+        if sync.is_null() {
+            return Err(GLContextError::GLError("glFenceSync"));
+        }
+
+        Ok(Fence {
+            sync: sync,
+        })
+    }
+
+    /// The sync object, for consumers waiting on it themselves. It's
+    /// still owned (and deleted) by the fence.
+    #[inline(always)]
+    pub fn get_sync(&self) -> GLsync {
+        self.sync
+    }
+
+    /// Blocks until the fence is signaled or `timeout` nanoseconds
+    /// pass, returning whether it was signaled.
+    pub fn client_wait(&self, timeout: u64) -> Result<bool> {
+        match unsafe { gl::ClientWaitSync(self.sync, SYNC_FLUSH_COMMANDS_BIT, timeout) } {
+            ALREADY_SIGNALED | CONDITION_SATISFIED => Ok(true),
+            TIMEOUT_EXPIRED => Ok(false),
+            _ => Err(GLContextError::GLError("glClientWaitSync")),
+        }
+    }
+
+    /// Makes the GPU wait for the fence before running the commands
+    /// issued afterwards in the current context, without blocking the
+    /// CPU. Useful when the consumer is another context of the same
+    /// share group.
+    pub fn gpu_wait(&self) {
+        unsafe {
+            gl::WaitSync(self.sync, 0, TIMEOUT_IGNORED);
+        }
+    }
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSync(self.sync);
+        }
+    }
+}
@@ -0,0 +1,298 @@
+//! Sized internal format selection.
+//!
+//! These map requested bit counts to the best supported sized internal
+//! format, so they can be reused for framebuffers not managed by the
+//! crate. They need the context to be current, to know its GL version.
+//! The selection itself is done for a given version, so it can be
+//! tested without a context.
+
+use gleam::gl;
+use gleam::gl::types::GLenum;
+
+use GLContextCapabilities;
+use {EXT_FLOAT_COLOR, EXT_PACKED_DEPTH_STENCIL};
+use GLContextError;
+use Result;
+use gl_feature::{GLVersion, get_gl_version};
+
+// NOTE: Not exposed by the GLES2 bindings
+const RGB8: GLenum = 0x8051;
+const RGBA8: GLenum = 0x8058;
+const RGB10_A2: GLenum = 0x8059;
+const RGB565: GLenum = 0x8D62;
+const RGBA16F: GLenum = 0x881A;
+const RGBA32F: GLenum = 0x8814;
+const DEPTH_COMPONENT24: GLenum = 0x81A6;
+const DEPTH_COMPONENT32F: GLenum = 0x8CAC;
+const DEPTH24_STENCIL8: GLenum = 0x88F0;
+const DEPTH32F_STENCIL8: GLenum = 0x8CAD;
+const STENCIL_INDEX16: GLenum = 0x8D49;
+
+/// Minimum amount of bits per channel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FormatBits {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+    pub depth: u8,
+    pub stencil: u8,
+}
+
+// GLES2 lacks most sized formats, which desktop GL and GLES 3.0 have
+fn is_gles2(version: GLVersion) -> bool {
+    let (is_gles, version) = version;
+    is_gles && version < 30
+}
+
+/// Returns the best supported color format with at least the requested
+/// bits. In order of preference:
+///
+///  * `RGBA8` (`RGB8` without alpha), on GL, GLES3, or for GLES2 if it
+///    can't be satisfied with the formats below
+///  * `RGB565`, `RGBA4` and `RGB5_A1`, the only formats GLES2 guarantees
+///  * `RGB10_A2` for up to 10 color bits and 2 alpha bits (GL/GLES3)
+///  * `RGBA16F` and `RGBA32F`, if float color buffers are supported
+pub fn choose_color_format(bits: &FormatBits, capabilities: &GLContextCapabilities)
+    -> Result<GLenum> {
+    color_format_for_version(bits, capabilities, get_gl_version())
+}
+
+fn color_format_for_version(bits: &FormatBits, capabilities: &GLContextCapabilities,
+                            version: GLVersion) -> Result<GLenum> {
+    let color = [bits.red, bits.green, bits.blue].iter().cloned().max().unwrap_or(0);
+    let gles2 = is_gles2(version);
+
+    if color <= 8 && bits.alpha <= 8 && !gles2 {
+        return Ok(if bits.alpha == 0 { RGB8 } else { RGBA8 });
+    }
+
+    if gles2 {
+        if bits.alpha == 0 && bits.red <= 5 && bits.green <= 6 && bits.blue <= 5 {
+            return Ok(RGB565);
+        }
+
+        if color <= 4 && bits.alpha <= 4 {
+            return Ok(gl::RGBA4);
+        }
+
+        if color <= 5 && bits.alpha <= 1 {
+            return Ok(gl::RGB5_A1);
+        }
+
+        // Maybe with GL_OES_rgb8_rgba8, the caller will have to probe
+        if color <= 8 && bits.alpha <= 8 {
+            return Ok(RGBA8);
+        }
+    }
+
+    if color <= 10 && bits.alpha <= 2 && !gles2 {
+        return Ok(RGB10_A2);
+    }
+
+    if capabilities.extensions.contains(EXT_FLOAT_COLOR) {
+        if color <= 16 && bits.alpha <= 16 {
+            return Ok(RGBA16F);
+        }
+
+        if color <= 32 && bits.alpha <= 32 {
+            return Ok(RGBA32F);
+        }
+    }
+
+    Err(GLContextError::Unsupported("No supported color format has that many bits"))
+}
+
+/// Returns the best supported depth-only format with at least the
+/// requested depth bits: `DEPTH_COMPONENT16` (always supported),
+/// `DEPTH_COMPONENT24` (GL/GLES3) or `DEPTH_COMPONENT32F` (GL3/GLES3).
+///
+/// Returns 0 if no depth bits are requested.
+pub fn choose_depth_format(bits: &FormatBits) -> Result<GLenum> {
+    depth_format_for_version(bits, get_gl_version())
+}
+
+fn depth_format_for_version(bits: &FormatBits, version: GLVersion) -> Result<GLenum> {
+    if bits.depth == 0 {
+        return Ok(0);
+    }
+
+    if bits.depth <= 16 {
+        return Ok(gl::DEPTH_COMPONENT16);
+    }
+
+    if bits.depth <= 24 && !is_gles2(version) {
+        return Ok(DEPTH_COMPONENT24);
+    }
+
+    // NOTE: Float depth buffers are core since GL 3.0
+    if bits.depth <= 32 && version.1 >= 30 {
+        return Ok(DEPTH_COMPONENT32F);
+    }
+
+    Err(GLContextError::Unsupported("No supported depth format has that many bits"))
+}
+
+/// Returns the best supported stencil-only format with at least the
+/// requested stencil bits: `STENCIL_INDEX8` (always supported), or
+/// `STENCIL_INDEX16` (desktop GL).
+///
+/// Returns 0 if no stencil bits are requested.
+pub fn choose_stencil_format(bits: &FormatBits) -> Result<GLenum> {
+    stencil_format_for_version(bits, get_gl_version())
+}
+
+fn stencil_format_for_version(bits: &FormatBits, version: GLVersion) -> Result<GLenum> {
+    if bits.stencil == 0 {
+        return Ok(0);
+    }
+
+    if bits.stencil <= 8 {
+        return Ok(gl::STENCIL_INDEX8);
+    }
+
+    let (is_gles, _) = version;
+    if bits.stencil <= 16 && !is_gles {
+        return Ok(STENCIL_INDEX16);
+    }
+
+    Err(GLContextError::Unsupported("No supported stencil format has that many bits"))
+}
+
+/// Returns a packed depth-stencil format with at least the requested
+/// bits, `DEPTH24_STENCIL8` or `DEPTH32F_STENCIL8` (GL3/GLES3), or an
+/// error if there's none, in which case separate depth and stencil
+/// buffers should be used.
+pub fn choose_depth_stencil_format(bits: &FormatBits, capabilities: &GLContextCapabilities)
+    -> Result<GLenum> {
+    depth_stencil_format_for_version(bits, capabilities, get_gl_version())
+}
+
+fn depth_stencil_format_for_version(bits: &FormatBits, capabilities: &GLContextCapabilities,
+                                    version: GLVersion) -> Result<GLenum> {
+    if !capabilities.extensions.contains(EXT_PACKED_DEPTH_STENCIL) || bits.stencil > 8 {
+        return Err(GLContextError::Unsupported("No supported packed depth-stencil format has that many bits"));
+    }
+
+    if bits.depth <= 24 {
+        return Ok(DEPTH24_STENCIL8);
+    }
+
+    if bits.depth <= 32 && version.1 >= 30 {
+        return Ok(DEPTH32F_STENCIL8);
+    }
+
+    Err(GLContextError::Unsupported("No supported packed depth-stencil format has that many bits"))
+}
+
+#[cfg(test)]
+mod tests {
+    use gleam::gl;
+
+    use GLContextCapabilities;
+    use GLExtensions;
+    use {EXT_FLOAT_COLOR, EXT_PACKED_DEPTH_STENCIL};
+    use super::{FormatBits, RGB8, RGBA8, RGB10_A2, RGB565, RGBA16F, RGBA32F};
+    use super::{DEPTH_COMPONENT24, DEPTH_COMPONENT32F, DEPTH24_STENCIL8, DEPTH32F_STENCIL8};
+    use super::STENCIL_INDEX16;
+    use super::{color_format_for_version, depth_format_for_version};
+    use super::{stencil_format_for_version, depth_stencil_format_for_version};
+
+    const GL21: (bool, u32) = (false, 21);
+    const GL30: (bool, u32) = (false, 30);
+    const GLES2: (bool, u32) = (true, 20);
+    const GLES3: (bool, u32) = (true, 30);
+
+    fn capabilities(extensions: GLExtensions) -> GLContextCapabilities {
+        GLContextCapabilities {
+            max_samples: 0,
+            max_renderbuffer_size: 4096,
+            max_color_attachments: 1,
+            max_draw_buffers: 1,
+            extensions: extensions,
+            gl_version: (false, 45),
+        }
+    }
+
+    fn color(red: u8, green: u8, blue: u8, alpha: u8) -> FormatBits {
+        FormatBits { red: red, green: green, blue: blue, alpha: alpha, depth: 0, stencil: 0 }
+    }
+
+    fn depth_stencil(depth: u8, stencil: u8) -> FormatBits {
+        FormatBits { red: 0, green: 0, blue: 0, alpha: 0, depth: depth, stencil: stencil }
+    }
+
+    #[test]
+    fn color_formats_on_gl_and_gles3() {
+        let plain = capabilities(GLExtensions::empty());
+        let float = capabilities(EXT_FLOAT_COLOR);
+
+        for &version in [GL21, GL30, GLES3].iter() {
+            assert_eq!(color_format_for_version(&color(8, 8, 8, 0), &plain, version), Ok(RGB8));
+            assert_eq!(color_format_for_version(&color(5, 6, 5, 0), &plain, version), Ok(RGB8));
+            assert_eq!(color_format_for_version(&color(8, 8, 8, 8), &plain, version), Ok(RGBA8));
+            assert_eq!(color_format_for_version(&color(10, 10, 10, 2), &plain, version), Ok(RGB10_A2));
+            assert!(color_format_for_version(&color(16, 16, 16, 16), &plain, version).is_err());
+            assert_eq!(color_format_for_version(&color(16, 16, 16, 16), &float, version), Ok(RGBA16F));
+            assert_eq!(color_format_for_version(&color(32, 32, 32, 32), &float, version), Ok(RGBA32F));
+            assert!(color_format_for_version(&color(33, 8, 8, 8), &float, version).is_err());
+        }
+    }
+
+    #[test]
+    fn color_formats_on_gles2() {
+        let plain = capabilities(GLExtensions::empty());
+        let float = capabilities(EXT_FLOAT_COLOR);
+
+        assert_eq!(color_format_for_version(&color(5, 6, 5, 0), &plain, GLES2), Ok(RGB565));
+        assert_eq!(color_format_for_version(&color(4, 4, 4, 4), &plain, GLES2), Ok(gl::RGBA4));
+        assert_eq!(color_format_for_version(&color(5, 5, 5, 1), &plain, GLES2), Ok(gl::RGB5_A1));
+        assert_eq!(color_format_for_version(&color(8, 8, 8, 0), &plain, GLES2), Ok(RGBA8));
+        assert_eq!(color_format_for_version(&color(8, 8, 8, 8), &plain, GLES2), Ok(RGBA8));
+        assert!(color_format_for_version(&color(10, 10, 10, 2), &plain, GLES2).is_err());
+        assert_eq!(color_format_for_version(&color(10, 10, 10, 2), &float, GLES2), Ok(RGBA16F));
+    }
+
+    #[test]
+    fn depth_formats() {
+        for &version in [GL21, GL30, GLES2, GLES3].iter() {
+            assert_eq!(depth_format_for_version(&depth_stencil(0, 0), version), Ok(0));
+            assert_eq!(depth_format_for_version(&depth_stencil(16, 0), version), Ok(gl::DEPTH_COMPONENT16));
+            assert!(depth_format_for_version(&depth_stencil(33, 0), version).is_err());
+        }
+
+        assert_eq!(depth_format_for_version(&depth_stencil(24, 0), GL21), Ok(DEPTH_COMPONENT24));
+        assert_eq!(depth_format_for_version(&depth_stencil(24, 0), GLES3), Ok(DEPTH_COMPONENT24));
+        assert!(depth_format_for_version(&depth_stencil(24, 0), GLES2).is_err());
+
+        assert!(depth_format_for_version(&depth_stencil(32, 0), GL21).is_err());
+        assert_eq!(depth_format_for_version(&depth_stencil(32, 0), GL30), Ok(DEPTH_COMPONENT32F));
+        assert_eq!(depth_format_for_version(&depth_stencil(32, 0), GLES3), Ok(DEPTH_COMPONENT32F));
+    }
+
+    #[test]
+    fn stencil_formats() {
+        for &version in [GL21, GLES2, GLES3].iter() {
+            assert_eq!(stencil_format_for_version(&depth_stencil(0, 0), version), Ok(0));
+            assert_eq!(stencil_format_for_version(&depth_stencil(1, 8), version), Ok(gl::STENCIL_INDEX8));
+            assert!(stencil_format_for_version(&depth_stencil(0, 17), version).is_err());
+        }
+
+        assert_eq!(stencil_format_for_version(&depth_stencil(0, 16), GL21), Ok(STENCIL_INDEX16));
+        assert!(stencil_format_for_version(&depth_stencil(0, 16), GLES3).is_err());
+    }
+
+    #[test]
+    fn depth_stencil_formats() {
+        let plain = capabilities(GLExtensions::empty());
+        let packed = capabilities(EXT_PACKED_DEPTH_STENCIL);
+
+        assert!(depth_stencil_format_for_version(&depth_stencil(24, 8), &plain, GL30).is_err());
+        assert_eq!(depth_stencil_format_for_version(&depth_stencil(24, 8), &packed, GLES2),
+                   Ok(DEPTH24_STENCIL8));
+        assert!(depth_stencil_format_for_version(&depth_stencil(24, 16), &packed, GL30).is_err());
+        assert!(depth_stencil_format_for_version(&depth_stencil(32, 8), &packed, GL21).is_err());
+        assert_eq!(depth_stencil_format_for_version(&depth_stencil(32, 8), &packed, GL30),
+                   Ok(DEPTH32F_STENCIL8));
+    }
+}
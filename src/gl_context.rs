@@ -1,47 +1,216 @@
 use geom::Size2D;
 use gleam::gl;
+use gleam::gl::types::{GLenum, GLint};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::Rc;
+use libc::c_void;
+use std::fmt;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 
 use NativeGLContextMethods;
 use GLContextAttributes;
 use GLContextCapabilities;
+use GLStateGuard;
+use BackendInfo;
 use DrawBuffer;
 use NativeGLContext;
+use ToGLSize;
+use GLContextError;
+use Result;
+use gl_errors;
+use gl_feature;
+use gl_context_capabilities::get_extension_names;
+use gl_validation::validate_request;
+use pending_deletions::PendingDeletions;
+use quad_blit;
 
+// NOTE: Not in the GLES2 bindings
+const CONTEXT_FLAGS: GLenum = 0x821E;
+const CONTEXT_FLAG_NO_ERROR_BIT_KHR: GLint = 0x8;
+
+// Ids are never reused, and zero means "unknown"
+static NEXT_CONTEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+// The id of the context we know is current in this thread, if any
+thread_local!(static CURRENT_CONTEXT_ID: Cell<usize> = Cell::new(0));
+
+/// Tells the crate that a context was made current (or released) without
+/// going through `GLContext::make_current`, so the next `make_current`
+/// call can't be skipped.
+///
+/// Applications mixing their own context management with `GLContext`
+/// must call this (in the same thread) after switching contexts.
+pub fn notify_external_make_current() {
+    CURRENT_CONTEXT_ID.with(|id| id.set(0));
+    gl_errors::set_no_error_context(false);
+    gl_feature::set_current_gl_version(None);
+}
+
+/// The id of the context known to be current in this thread,
+/// or zero if unknown.
+pub fn current_context_id() -> usize {
+    CURRENT_CONTEXT_ID.with(|current| current.get())
+}
+
+/// Returns the id and the pending deletion queue of `context`, so
+/// objects created with it can be deleted later if they're dropped
+/// while it isn't current.
+pub fn deletion_queue_of(context: &GLContext) -> (usize, Rc<RefCell<PendingDeletions>>) {
+    (context.id, context.pending_deletions.clone())
+}
 
 pub struct GLContext {
+    id: usize,
     native_context: NativeGLContext,
     draw_buffer: Option<DrawBuffer>,
     attributes: GLContextAttributes,
     capabilities: GLContextCapabilities,
+    backend_info: BackendInfo,
+    // Queried once, since parsing them isn't cheap
+    extension_names: HashSet<String>,
+    pending_deletions: Rc<RefCell<PendingDeletions>>,
+    // Whether it was created without error checking
+    no_error: bool,
+    // Results of `is_config_supported`, there are usually only a few
+    supported_configs: RefCell<Vec<(GLContextAttributes, bool)>>,
 }
 
 impl GLContext {
-    pub fn create_headless(size: Size2D<i32>) -> Result<GLContext, &'static str> {
-        let native_context = try!(NativeGLContext::create_headless(size));
+    pub fn create_headless<S: ToGLSize>(size: S) -> Result<GLContext> {
+        GLContext::create_headless_with_error_mode(try!(size.to_gl_size()), false)
+    }
+
+    fn create_headless_with_error_mode(size: Size2D<i32>, no_error: bool) -> Result<GLContext> {
+        let native_context = if no_error {
+            try!(NativeGLContext::create_headless_no_error(size))
+        } else {
+            try!(NativeGLContext::create_headless(size))
+        };
 
         try!(native_context.make_current());
 
+        // Forget the version of the previously current context
+        gl_feature::set_current_gl_version(None);
+
+        // The backend may have fallen back to a regular context
+        let no_error = no_error && {
+            let mut flags = 0;
+            unsafe {
+                gl::GetIntegerv(CONTEXT_FLAGS, &mut flags);
+
+                // GL_CONTEXT_FLAGS doesn't exist in GLES2
+                if flags & CONTEXT_FLAG_NO_ERROR_BIT_KHR == 0 {
+                    while gl::GetError() != gl::NO_ERROR {}
+                }
+            }
+            flags & CONTEXT_FLAG_NO_ERROR_BIT_KHR != 0
+        };
+
+        if no_error {
+            debug!("Created a no-error context");
+        }
+
+        gl_errors::set_no_error_context(no_error);
+
+        let backend_info = BackendInfo::detect(native_context.backend_kind());
+        let extension_names = get_extension_names();
+        let capabilities = GLContextCapabilities::detect_with_extensions(&extension_names);
+        gl_feature::set_current_gl_version(Some(capabilities.gl_version));
+
+        debug!("Created a {}x{} headless context: {:?}", size.width, size.height, backend_info);
+
+        let id = NEXT_CONTEXT_ID.fetch_add(1, Ordering::SeqCst) + 1;
+        CURRENT_CONTEXT_ID.with(|current| current.set(id));
+
         Ok(GLContext {
+            id: id,
             native_context: native_context,
             draw_buffer: None,
             attributes: GLContextAttributes::any(),
-            capabilities: GLContextCapabilities::detect()
+            capabilities: capabilities,
+            backend_info: backend_info,
+            extension_names: extension_names,
+            pending_deletions: Rc::new(RefCell::new(PendingDeletions::new())),
+            no_error: no_error,
+            supported_configs: RefCell::new(vec![]),
         })
     }
 
-    pub fn create_offscreen(size: Size2D<i32>, attributes: GLContextAttributes) -> Result<GLContext, &'static str> {
-        let mut context = try!(GLContext::create_headless(size));
+    pub fn create_offscreen<S: ToGLSize>(size: S, attributes: GLContextAttributes) -> Result<GLContext> {
+        let size = try!(size.to_gl_size());
+        let mut context = try!(GLContext::create_headless_with_error_mode(size, attributes.no_error));
 
         context.attributes = attributes;
 
+        try!(validate_request(&size, &context.attributes, &context.capabilities));
+
         try!(context.init_offscreen(size));
 
         Ok(context)
     }
 
-    #[inline(always)]
-    pub fn make_current(&self) -> Result<(), &'static str> {
-        self.native_context.make_current()
+    /// Like `create_offscreen`, but if creation fails with `attributes`,
+    /// retries progressively dropping the least critical ones: first
+    /// antialiasing, then the stencil buffer.
+    ///
+    /// NOTE: Debug contexts would be dropped first, but contexts can't
+    /// be requested as such yet (`GLContextAttributes` has no debug
+    /// attribute), so there's nothing to drop before antialiasing.
+    ///
+    /// The attributes that succeeded can be queried with `borrow_attributes`.
+    pub fn new_with_fallback<S: ToGLSize>(size: S, attributes: GLContextAttributes)
+        -> Result<GLContext> {
+        let size = try!(size.to_gl_size());
+
+        let mut attributes = attributes;
+        let mut result = GLContext::create_offscreen(size, attributes);
+
+        if result.is_err() && (attributes.antialias || attributes.antialias_quality.is_some()) {
+            warn!("Couldn't create the offscreen context, retrying without antialiasing");
+            attributes.antialias = false;
+            attributes.antialias_quality = None;
+            result = GLContext::create_offscreen(size, attributes);
+        }
+
+        if result.is_err() && attributes.stencil {
+            warn!("Couldn't create the offscreen context, retrying without stencil buffer");
+            attributes.stencil = false;
+            result = GLContext::create_offscreen(size, attributes);
+        }
+
+        result
+    }
+
+    /// Makes the context current. This is a no-op if we know it's already
+    /// current in this thread (see `notify_external_make_current`).
+    ///
+    /// Objects that were dropped while the context wasn't current are
+    /// deleted here (see `flush_pending_deletions`).
+    pub fn make_current(&self) -> Result<()> {
+        if current_context_id() != self.id {
+            try!(self.native_context.make_current());
+            CURRENT_CONTEXT_ID.with(|current| current.set(self.id));
+            gl_errors::set_no_error_context(self.no_error);
+            gl_feature::set_current_gl_version(Some(self.capabilities.gl_version));
+        }
+
+        self.flush_pending_deletions();
+
+        Ok(())
+    }
+
+    /// Deletes the objects of draw buffers that were dropped while this
+    /// context wasn't current.
+    ///
+    /// NOTE: Assumes the context is current. `make_current` calls it.
+    pub fn flush_pending_deletions(&self) {
+        let mut pending_deletions = self.pending_deletions.borrow_mut();
+        if !pending_deletions.is_empty() {
+            debug!("Deleting GL objects orphaned while the context wasn't current");
+            pending_deletions.flush();
+        }
     }
 
     #[inline(always)]
@@ -49,6 +218,19 @@ impl GLContext {
         self.native_context.is_current()
     }
 
+    /// Enables strict GL error checking. Note that this is a global
+    /// setting, affecting every context. See `set_strict_errors`.
+    pub fn set_strict_errors(&self, enabled: bool) {
+        gl_errors::set_strict_errors(enabled);
+    }
+
+    /// Whether the context was created without error checking
+    /// (see `GLContextAttributes::no_error`). If so, the crate never
+    /// calls `glGetError` while it's current.
+    pub fn is_no_error(&self) -> bool {
+        self.no_error
+    }
+
     // Allow borrowing these unmutably
     pub fn borrow_attributes(&self) -> &GLContextAttributes {
         &self.attributes
@@ -57,32 +239,178 @@ impl GLContext {
     pub fn borrow_capabilities(&self) -> &GLContextCapabilities {
         &self.capabilities
     }
+
+    pub fn borrow_draw_buffer(&self) -> Option<&DrawBuffer> {
+        self.draw_buffer.as_ref()
+    }
+
+    /// Resizes the draw buffer created with the context (see
+    /// `create_offscreen`), and sets the viewport and scissor box to
+    /// the new size, as on creation. The contents are lost.
+    ///
+    /// Draw buffers created separately with `DrawBuffer::new` must be
+    /// resized with `DrawBuffer::resize` instead.
+    pub fn resize<S: ToGLSize>(&mut self, size: S) -> Result<()> {
+        let size = try!(size.to_gl_size());
+
+        try!(self.make_current());
+
+        let draw_buffer = match self.draw_buffer {
+            Some(ref mut draw_buffer) => draw_buffer,
+            None => return Err(GLContextError::InvalidOperation("The context has no draw buffer (see create_offscreen)")),
+        };
+
+        try!(draw_buffer.resize(size));
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, draw_buffer.get_framebuffer());
+            gl::Scissor(0, 0, size.width, size.height);
+            gl::Viewport(0, 0, size.width, size.height);
+        }
+
+        Ok(())
+    }
+
+    /// Whether a draw buffer of `size` with `attributes` can be created,
+    /// without allocating one of that size: the request is validated,
+    /// and a 1x1 draw buffer with the same formats is created, checked
+    /// for completeness and deleted.
+    ///
+    /// Probe results are cached per attribute set, and the GL state
+    /// is preserved.
+    pub fn is_config_supported<S: ToGLSize>(&self, attributes: GLContextAttributes, size: S)
+        -> bool {
+        let size = match size.to_gl_size() {
+            Ok(size) => size,
+            Err(_) => return false,
+        };
+
+        if validate_request(&size, &attributes, &self.capabilities).is_err() {
+            return false;
+        }
+
+        let cached = self.supported_configs.borrow()
+                                           .iter()
+                                           .find(|&&(ref attrs, _)| *attrs == attributes)
+                                           .map(|&(_, supported)| supported);
+        if let Some(supported) = cached {
+            return supported;
+        }
+
+        if self.make_current().is_err() {
+            return false;
+        }
+
+        let supported = {
+            let _guard = GLStateGuard::new();
+            match DrawBuffer::new_with_attributes(self, Size2D(1, 1), attributes) {
+                Ok(draw_buffer) => draw_buffer.is_complete(),
+                Err(err) => {
+                    debug!("Configuration {:?} not supported: {:?}", attributes, err);
+                    false
+                }
+            }
+        };
+
+        self.supported_configs.borrow_mut().push((attributes, supported));
+
+        supported
+    }
+
+    /// Whether the context exposes the extension `name`
+    /// (like "GL_EXT_texture_filter_anisotropic").
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extension_names.contains(name)
+    }
+
+    /// The backend and driver that created the context, as
+    /// detected on creation.
+    pub fn backend_info(&self) -> &BackendInfo {
+        &self.backend_info
+    }
+
+    /// Returns the address of a GL entry point of this context, or
+    /// null if it doesn't exist. Useful for extension functions the
+    /// `gleam` bindings don't load.
+    pub fn get_proc_address(&self, name: &str) -> *const c_void {
+        self.native_context.get_proc_address(name)
+    }
+}
+
+impl fmt::Debug for GLContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GLContext {{ id: {}, backend_info: {:?}, attributes: {:?} }}",
+               self.id, self.backend_info, self.attributes)
+    }
+}
+
+// The draw buffer must be destroyed while the native context still
+// exists (and is current), so we can't rely on the field drop order.
+impl Drop for GLContext {
+    fn drop(&mut self) {
+        // NOTE: This also deletes the pending objects
+        let is_current = self.make_current().is_ok();
+
+        if is_current {
+            quad_blit::delete_blitter(self.id);
+        }
+
+        if let Some(draw_buffer) = self.draw_buffer.take() {
+            if is_current {
+                drop(draw_buffer);
+            } else {
+                // Better leak than delete objects of another context
+                error!("Couldn't make the context current on drop, leaking its draw buffer");
+                mem::forget(draw_buffer);
+            }
+        }
+
+        if !self.pending_deletions.borrow().is_empty() {
+            error!("Leaking GL objects orphaned while the context wasn't current");
+        }
+
+        // Draw buffers outliving us must not touch GL on drop, since
+        // their objects go away with the native context
+        self.pending_deletions.borrow_mut().mark_context_destroyed();
+
+        CURRENT_CONTEXT_ID.with(|current| {
+            if current.get() == self.id {
+                current.set(0);
+                gl_errors::set_no_error_context(false);
+                gl_feature::set_current_gl_version(None);
+            }
+        });
+    }
 }
 
 
 trait GLContextPrivateMethods {
-    fn init_offscreen(&mut self, Size2D<i32>) -> Result<(), &'static str>;
-    fn create_draw_buffer(&mut self, Size2D<i32>) -> Result<(), &'static str>;
+    fn init_offscreen(&mut self, Size2D<i32>) -> Result<()>;
+    fn create_draw_buffer(&mut self, Size2D<i32>) -> Result<()>;
 }
 
 impl GLContextPrivateMethods for GLContext {
     // FIXME(ecoal95): initial resizing should be handled here,
     //   generic resizing should be handled in the screen buffer/draw buffer
-    fn init_offscreen(&mut self, size: Size2D<i32>) -> Result<(), &'static str> {
-        // try!(self.create_draw_buffer(size));
+    fn init_offscreen(&mut self, size: Size2D<i32>) -> Result<()> {
+        try!(self.create_draw_buffer(size));
 
         self.make_current().unwrap();
 
+        let framebuffer = self.draw_buffer.as_ref().unwrap().get_framebuffer();
+
         unsafe {
-            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
             gl::Scissor(0, 0, size.width, size.height);
             gl::Viewport(0, 0, size.width, size.height);
         }
 
+        self.draw_buffer.as_ref().unwrap().apply_dither();
+
         Ok(())
     }
 
-    fn create_draw_buffer(&mut self, size: Size2D<i32>) -> Result<(), &'static str> {
+    fn create_draw_buffer(&mut self, size: Size2D<i32>) -> Result<()> {
         self.draw_buffer = Some(try!(DrawBuffer::new(&self, size)));
         Ok(())
     }
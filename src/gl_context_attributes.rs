@@ -1,7 +1,76 @@
+use gleam::gl;
+use gleam::gl::types::GLenum;
+
+
+/// What to do when the requested amount of antialiasing samples
+/// is not available.
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum AntialiasPolicy {
+    /// Fail to create the draw buffer
+    Strict,
+    /// Use the maximum amount of samples available (which may be zero)
+    BestEffort,
+}
+
+/// A coarse antialiasing quality, as usually presented to users. It
+/// maps to an amount of samples clamped to what the implementation
+/// supports, so requesting it never fails.
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum AntialiasQuality {
+    None,
+    /// 2 samples
+    Low,
+    /// 4 samples
+    Medium,
+    /// 8 samples
+    High,
+}
+
+impl AntialiasQuality {
+    /// The amount of samples to use with `max_samples` available.
+    ///
+    /// NOTE: `High` doesn't go past 8 even if more are available,
+    /// since those are much slower and barely look any better.
+    pub fn samples(&self, max_samples: i32) -> i32 {
+        let samples = match *self {
+            AntialiasQuality::None => 0,
+            AntialiasQuality::Low => 2,
+            AntialiasQuality::Medium => 4,
+            AntialiasQuality::High => 8,
+        };
+
+        if samples > max_samples { max_samples } else { samples }
+    }
+}
+
+/// Sampling parameters applied to the color texture created with the
+/// `color_texture` attribute, so it can be sampled right away.
+///
+/// NOTE: The texture has a single mip level, so mipmap minification
+/// filters make it incomplete until mipmaps are generated.
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub struct TextureParameters {
+    pub min_filter: GLenum,
+    pub mag_filter: GLenum,
+    pub wrap_s: GLenum,
+    pub wrap_t: GLenum,
+}
+
+impl TextureParameters {
+    /// `GL_LINEAR` filtering and `GL_CLAMP_TO_EDGE` wrapping
+    pub fn default() -> TextureParameters {
+        TextureParameters {
+            min_filter: gl::LINEAR,
+            mag_filter: gl::LINEAR,
+            wrap_s: gl::CLAMP_TO_EDGE,
+            wrap_t: gl::CLAMP_TO_EDGE,
+        }
+    }
+}
 
 /// This structure represents the attributes the context must support
 /// It's almost (if not) identical to WebGLGLContextAttributes
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq)]
 pub struct GLContextAttributes {
     pub alpha: bool,
     pub depth: bool,
@@ -9,6 +78,56 @@ pub struct GLContextAttributes {
     pub antialias: bool,
     pub premultiplied_alpha: bool,
     pub preserve_drawing_buffer: bool,
+    // Minimum amount of alpha bits the color buffer must have,
+    // only taken into account if `alpha` is true.
+    pub min_alpha_bits: u8,
+    // Preferred amount of stencil bits. STENCIL_INDEX8 is used
+    // when the preferred format isn't available.
+    pub stencil_bits: u8,
+    // Requested amount of antialiasing samples, only taken into
+    // account if `antialias` is true.
+    pub samples: i32,
+    pub antialias_policy: AntialiasPolicy,
+    // If set, `antialias`, `samples` and `antialias_policy` are ignored,
+    // and the amount of samples is derived from the quality instead.
+    // The resolved amount is `DrawBuffer::samples`.
+    pub antialias_quality: Option<AntialiasQuality>,
+    // Amount of color attachments, for multiple render targets
+    pub color_attachments: i32,
+    // Create a framebuffer without attachments, using the default
+    // framebuffer parameters instead (GL 4.3 / GLES 3.1). Rendering
+    // then only has side effects, like image stores.
+    pub no_attachments: bool,
+    // Some drivers reject stencil-only framebuffers (as unsupported).
+    // If this is true, a depth buffer is added in that case, so the
+    // framebuffer has more attachments than requested.
+    pub stencil_only_workaround: bool,
+    // Create the color attachment as a texture, so it can be sampled
+    // later, instead of as a render buffer.
+    pub color_texture: bool,
+    // Sampling parameters of the color texture, if `color_texture`
+    pub color_texture_parameters: TextureParameters,
+    // Whether to enable dithering (GL_DITHER) when binding the draw
+    // buffer. It's GL's default, and hides the banding of low-bit
+    // formats like the RGBA4 fallback.
+    pub dither: bool,
+    // Prefer a BGRA8 color buffer (GL_BGRA8_EXT), the native format of
+    // some compositors, where supported (see `EXT_BGRA8_FORMAT`).
+    // `DrawBuffer::color_format` tells whether it was used.
+    pub bgra: bool,
+    // Treat the depth and stencil buffers as transient: their contents
+    // don't survive `DrawBuffer::bind` (or `invalidate_transient`).
+    // Where glInvalidateFramebuffer is available (GL 4.3 / GLES 3.0),
+    // they're invalidated then, so tiled GPUs never load or store them,
+    // and may not even back them with memory. Otherwise it has no effect.
+    pub transient: bool,
+    // Request a context without error checking (GL_KHR_no_error), if
+    // the backend can create one (see `GLContext::is_no_error`). Errors
+    // are then undefined behavior instead of being reported, so the
+    // crate never calls glGetError: format fallbacks, out of memory
+    // detection and strict errors don't work. Only worth it for
+    // already debugged code paths.
+    pub no_error: bool,
     // TODO: Some Android devices dont't support
     //   32 bits per pixel, eventually we may want
     //   to allow it
@@ -23,6 +142,20 @@ impl GLContextAttributes {
             antialias: false,
             premultiplied_alpha: false,
             preserve_drawing_buffer: false,
+            min_alpha_bits: 0,
+            stencil_bits: 8,
+            samples: 4,
+            antialias_policy: AntialiasPolicy::Strict,
+            antialias_quality: None,
+            color_attachments: 1,
+            no_attachments: false,
+            stencil_only_workaround: true,
+            color_texture: false,
+            color_texture_parameters: TextureParameters::default(),
+            dither: true,
+            bgra: false,
+            transient: false,
+            no_error: false,
         }
     }
 
@@ -36,7 +169,21 @@ impl GLContextAttributes {
             stencil: false,
             antialias: false,
             premultiplied_alpha: true,
-            preserve_drawing_buffer: false
+            preserve_drawing_buffer: false,
+            min_alpha_bits: 0,
+            stencil_bits: 8,
+            samples: 4,
+            antialias_policy: AntialiasPolicy::Strict,
+            antialias_quality: None,
+            color_attachments: 1,
+            no_attachments: false,
+            stencil_only_workaround: true,
+            color_texture: false,
+            color_texture_parameters: TextureParameters::default(),
+            dither: true,
+            bgra: false,
+            transient: false,
+            no_error: false,
         }
     }
 }
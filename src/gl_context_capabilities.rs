@@ -1,6 +1,39 @@
-// use gleam::gl;
-use gleam::gl::{GLint};
+use gleam::gl;
+use gleam::gl::types::{GLenum, GLint, GLuint};
+use geom::Size2D;
+use std::cmp;
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::str;
 use GLFeature;
+use GLContextError;
+use Result;
+use gl_feature::{GLVersion, get_gl_version};
+
+// NOTE: Not exposed by the GLES bindings
+const RGBA16F: GLenum = 0x881A;
+const MAX_SAMPLES: GLenum = 0x8D57;
+const MAX_COLOR_ATTACHMENTS: GLenum = 0x8CDF;
+const MAX_DRAW_BUFFERS: GLenum = 0x8824;
+const NUM_EXTENSIONS: GLenum = 0x821D;
+const FRAMEBUFFER_RENDERABLE: GLenum = 0x8289;
+const NUM_SAMPLE_COUNTS: GLenum = 0x9380;
+const SAMPLES: GLenum = 0x80A9;
+
+bitflags! {
+    #[doc = "Extension-based features, either core in the current"]
+    #[doc = "GL version or exposed via extensions."]
+    flags GLExtensions: u32 {
+        const EXT_PACKED_DEPTH_STENCIL = 0b00000001,
+        const EXT_FLOAT_COLOR          = 0b00000010,
+        const EXT_SRGB                 = 0b00000100,
+        const EXT_MULTISAMPLE          = 0b00001000,
+        const EXT_TIMER_QUERY          = 0b00010000,
+        const EXT_BGRA_READ            = 0b00100000,
+        const EXT_MULTIVIEW            = 0b01000000,
+        const EXT_BGRA8_FORMAT         = 0b10000000,
+    }
+}
 
 /// This is a cross-platform struct, that every GLContext implementation
 /// should have under the field `capabilities`, as a public field
@@ -10,21 +43,212 @@ use GLFeature;
 pub struct GLContextCapabilities {
     // max antialising samples, 0 if no antialising supported
     pub max_samples: GLint,
+    pub max_renderbuffer_size: GLint,
+    // MRT limits, 1 if MRT is not supported (GLES2)
+    pub max_color_attachments: GLint,
+    pub max_draw_buffers: GLint,
+    pub extensions: GLExtensions,
+    // Queried once, see `get_gl_version`
+    pub gl_version: GLVersion,
+}
+
+/// Returns the extension list of the current context.
+/// Core contexts don't allow `glGetString(GL_EXTENSIONS)`,
+/// so we use `glGetStringi` when available.
+pub fn get_extension_names() -> HashSet<String> {
+    let (is_gles, version) = get_gl_version();
+
+    unsafe {
+        if !is_gles && version >= 30 {
+            let mut count: GLint = 0;
+            gl::GetIntegerv(NUM_EXTENSIONS, &mut count);
+
+            return (0..count as GLuint).filter_map(|i| {
+                let ptr = gl::GetStringi(gl::EXTENSIONS, i);
+                if ptr.is_null() {
+                    None
+                } else {
+                    str::from_utf8(CStr::from_ptr(ptr as *const _).to_bytes())
+                        .ok().map(|name| name.to_string())
+                }
+            }).collect();
+        }
+
+        let ptr = gl::GetString(gl::EXTENSIONS);
+        if ptr.is_null() {
+            return HashSet::new();
+        }
+
+        let extensions = str::from_utf8(CStr::from_ptr(ptr as *const _).to_bytes()).unwrap_or("");
+        extensions.split(' ').filter(|name| !name.is_empty())
+                             .map(|name| name.to_string())
+                             .collect()
+    }
+}
+
+fn detect_extensions(names: &HashSet<String>) -> GLExtensions {
+    let (is_gles, version) = get_gl_version();
+
+    let has = |candidates: &[&str]| {
+        candidates.iter().any(|candidate| names.contains(*candidate))
+    };
+
+    // GL 3.0 and GLES 3.0 share most of these as core features
+    let is_gl3 = version >= 30;
+
+    let mut extensions = GLExtensions::empty();
+
+    if is_gl3 || has(&["GL_EXT_packed_depth_stencil", "GL_OES_packed_depth_stencil"]) {
+        extensions.insert(EXT_PACKED_DEPTH_STENCIL);
+    }
+
+    if (!is_gles && is_gl3) || has(&["GL_ARB_color_buffer_float", "GL_EXT_color_buffer_float"]) {
+        extensions.insert(EXT_FLOAT_COLOR);
+    }
+
+    if is_gl3 || has(&["GL_EXT_sRGB", "GL_ARB_framebuffer_sRGB", "GL_EXT_framebuffer_sRGB"]) {
+        extensions.insert(EXT_SRGB);
+    }
+
+    if is_gl3 || has(&["GL_EXT_framebuffer_multisample",
+                       "GL_ANGLE_framebuffer_multisample",
+                       "GL_APPLE_framebuffer_multisample"]) {
+        extensions.insert(EXT_MULTISAMPLE);
+    }
+
+    if (!is_gles && version >= 33) || has(&["GL_ARB_timer_query",
+                                            "GL_EXT_timer_query",
+                                            "GL_EXT_disjoint_timer_query"]) {
+        extensions.insert(EXT_TIMER_QUERY);
+    }
+
+    // BGRA is core since GL 1.2
+    if !is_gles || has(&["GL_EXT_read_format_bgra"]) {
+        extensions.insert(EXT_BGRA_READ);
+    }
+
+    // BGRA8 color buffers (GL_BGRA8_EXT), only in GLES
+    if is_gles && has(&["GL_EXT_texture_format_BGRA8888", "GL_APPLE_texture_format_BGRA8888"]) {
+        extensions.insert(EXT_BGRA8_FORMAT);
+    }
+
+    // Only through the extension, there's no core equivalent
+    if has(&["GL_OVR_multiview2"]) {
+        extensions.insert(EXT_MULTIVIEW);
+    }
+
+    // With GL 4.2 we can ask whether float formats are actually
+    // renderable, instead of trusting the extension list.
+    if extensions.contains(EXT_FLOAT_COLOR) && !is_gles && version >= 42 {
+        let mut renderable: GLint = 0;
+        unsafe {
+            gl::GetInternalformativ(gl::RENDERBUFFER, RGBA16F,
+                                    FRAMEBUFFER_RENDERABLE, 1, &mut renderable);
+        }
+
+        // GL_NONE means no support at all
+        if renderable == 0 {
+            extensions.remove(EXT_FLOAT_COLOR);
+        }
+    }
+
+    extensions
 }
 
 impl GLContextCapabilities {
-    #[allow(unused_mut)]
     pub fn detect() -> GLContextCapabilities {
+        GLContextCapabilities::detect_with_extensions(&get_extension_names())
+    }
+
+    /// Like `detect`, but using an already queried extension list
+    /// (see `GLContext::has_extension`).
+    pub fn detect_with_extensions(extension_names: &HashSet<String>) -> GLContextCapabilities {
         let mut capabilities = GLContextCapabilities {
             max_samples: 0,
+            max_renderbuffer_size: 0,
+            max_color_attachments: 1,
+            max_draw_buffers: 1,
+            extensions: detect_extensions(extension_names),
+            gl_version: get_gl_version(),
         };
 
+        unsafe {
+            gl::GetIntegerv(gl::MAX_RENDERBUFFER_SIZE, &mut capabilities.max_renderbuffer_size);
+        }
+
+        let (is_gles, version) = get_gl_version();
+        if !is_gles || version >= 30 {
+            unsafe {
+                gl::GetIntegerv(MAX_COLOR_ATTACHMENTS, &mut capabilities.max_color_attachments);
+                gl::GetIntegerv(MAX_DRAW_BUFFERS, &mut capabilities.max_draw_buffers);
+            }
+        }
 
         if GLFeature::is_supported(GLFeature::FramebufferMultisample) {
-            // FIXME(ecoal95): uncomment me when we have cross-system constants
-            // unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut capabilities.max_samples as *mut GLint); };
+            unsafe { gl::GetIntegerv(MAX_SAMPLES, &mut capabilities.max_samples as *mut GLint); };
         }
 
         capabilities
     }
+
+    /// Returns the antialiasing sample counts supported for render buffers
+    /// of the given `format`, in descending order.
+    ///
+    /// Without `glGetInternalformativ` (GL 4.2 / GLES 3.0), this assumes
+    /// every power of two up to `max_samples` is supported.
+    ///
+    /// NOTE: Requires the context to be current.
+    pub fn supported_sample_counts(&self, format: GLenum) -> Vec<GLint> {
+        let mut counts = if GLFeature::is_supported(GLFeature::InternalformatQuery) {
+            let mut count: GLint = 0;
+            unsafe {
+                gl::GetInternalformativ(gl::RENDERBUFFER, format, NUM_SAMPLE_COUNTS, 1, &mut count);
+            }
+
+            let mut counts: Vec<GLint> = vec![0; count as usize];
+            if count > 0 {
+                unsafe {
+                    gl::GetInternalformativ(gl::RENDERBUFFER, format, SAMPLES,
+                                            count, counts.as_mut_ptr());
+                }
+            }
+            counts
+        } else {
+            let mut counts = vec![];
+            let mut samples = 2;
+            while samples <= self.max_samples {
+                counts.push(samples);
+                samples *= 2;
+            }
+            counts
+        };
+
+        counts.sort_by(|a, b| b.cmp(a));
+        counts
+    }
+
+    /// `GL_MAX_COLOR_ATTACHMENTS`, or 1 on GLES2.
+    pub fn max_color_attachments(&self) -> GLint {
+        self.max_color_attachments
+    }
+
+    /// `GL_MAX_DRAW_BUFFERS`, or 1 on GLES2.
+    pub fn max_draw_buffers(&self) -> GLint {
+        self.max_draw_buffers
+    }
+
+    /// The maximum amount of color attachments we can render
+    /// to at the same time.
+    pub fn max_render_targets(&self) -> GLint {
+        cmp::min(self.max_color_attachments, self.max_draw_buffers)
+    }
+
+    /// Checks `size` is within the implementation limits.
+    pub fn check_size(&self, size: &Size2D<i32>) -> Result<()> {
+        if size.width > self.max_renderbuffer_size || size.height > self.max_renderbuffer_size {
+            return Err(GLContextError::InvalidRequest("Size exceeds GL_MAX_RENDERBUFFER_SIZE"));
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,49 @@
+use gleam::gl::types::GLenum;
+use std::error::Error;
+use std::fmt;
+use std::result;
+
+use describe_framebuffer_status;
+
+/// The errors returned by the crate. Every variant but
+/// `IncompleteFramebuffer` carries a human readable description.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GLContextError {
+    /// The native context couldn't be created or made current
+    NativeContext(&'static str),
+    /// The request is invalid, or exceeds the implementation limits
+    InvalidRequest(&'static str),
+    /// The implementation lacks a required feature, extension or format
+    Unsupported(&'static str),
+    /// The operation isn't possible with this draw buffer configuration
+    InvalidOperation(&'static str),
+    /// The framebuffer isn't complete, with the `glCheckFramebufferStatus`
+    /// result
+    IncompleteFramebuffer(GLenum),
+    /// A GL call failed (see `set_strict_errors`)
+    GLError(&'static str),
+    /// The implementation ran out of memory (`GL_OUT_OF_MEMORY`)
+    OutOfMemory(&'static str),
+}
+
+pub type Result<T> = result::Result<T, GLContextError>;
+
+impl Error for GLContextError {
+    fn description(&self) -> &str {
+        match *self {
+            GLContextError::NativeContext(description) |
+            GLContextError::InvalidRequest(description) |
+            GLContextError::Unsupported(description) |
+            GLContextError::InvalidOperation(description) |
+            GLContextError::GLError(description) |
+            GLContextError::OutOfMemory(description) => description,
+            GLContextError::IncompleteFramebuffer(status) => describe_framebuffer_status(status),
+        }
+    }
+}
+
+impl fmt::Display for GLContextError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
@@ -0,0 +1,93 @@
+use gleam::gl;
+use gleam::gl::types::GLenum;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering, ATOMIC_BOOL_INIT};
+
+use GLContextError;
+use Result;
+
+// NOTE: Some of these only exist in desktop GL, GLES2 or GLES3,
+// so define them all here
+const FRAMEBUFFER_COMPLETE: GLenum = 0x8CD5;
+const FRAMEBUFFER_UNDEFINED: GLenum = 0x8219;
+const FRAMEBUFFER_INCOMPLETE_ATTACHMENT: GLenum = 0x8CD6;
+const FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT: GLenum = 0x8CD7;
+const FRAMEBUFFER_INCOMPLETE_DIMENSIONS: GLenum = 0x8CD9;
+const FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER: GLenum = 0x8CDB;
+const FRAMEBUFFER_INCOMPLETE_READ_BUFFER: GLenum = 0x8CDC;
+const FRAMEBUFFER_UNSUPPORTED: GLenum = 0x8CDD;
+const FRAMEBUFFER_INCOMPLETE_MULTISAMPLE: GLenum = 0x8D56;
+const FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS: GLenum = 0x8DA8;
+
+static STRICT_ERRORS: AtomicBool = ATOMIC_BOOL_INIT;
+
+// Whether the context current in this thread is a no-error one
+thread_local!(static NO_ERROR_CONTEXT: Cell<bool> = Cell::new(false));
+
+/// Tells the crate whether the context current in this thread was
+/// created without error checking (GL_KHR_no_error). `GLContext`
+/// calls it when it's made current.
+pub fn set_no_error_context(no_error: bool) {
+    NO_ERROR_CONTEXT.with(|current| current.set(no_error));
+}
+
+/// `glGetError`, except that it returns `GL_NO_ERROR` without calling
+/// into GL if the current context is a no-error one.
+///
+/// Every error check of the crate goes through this.
+pub fn get_error() -> GLenum {
+    if NO_ERROR_CONTEXT.with(|current| current.get()) {
+        return gl::NO_ERROR;
+    }
+
+    unsafe { gl::GetError() }
+}
+
+/// Enables or disables strict error checking for every context.
+///
+/// When enabled, internal GL operations check `glGetError` and
+/// return an error instead of (only) asserting in debug builds.
+/// It's disabled by default because `glGetError` can force a
+/// synchronization with the driver.
+pub fn set_strict_errors(enabled: bool) {
+    STRICT_ERRORS.store(enabled, Ordering::SeqCst);
+}
+
+#[inline(always)]
+pub fn strict_errors_enabled() -> bool {
+    STRICT_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Checks for GL errors after an operation, returning an error
+/// described by `operation` if strict checking is on.
+pub fn check_gl_error(operation: &'static str) -> Result<()> {
+    if !strict_errors_enabled() {
+        debug_assert!(get_error() == gl::NO_ERROR);
+        return Ok(());
+    }
+
+    match get_error() {
+        gl::NO_ERROR => Ok(()),
+        gl::OUT_OF_MEMORY => Err(GLContextError::OutOfMemory(operation)),
+        _ => Err(GLContextError::GLError(operation)),
+    }
+}
+
+/// Returns a human readable description of a `glCheckFramebufferStatus`
+/// result.
+pub fn describe_framebuffer_status(status: GLenum) -> &'static str {
+    match status {
+        FRAMEBUFFER_COMPLETE => "The framebuffer is complete",
+        FRAMEBUFFER_UNDEFINED => "The framebuffer is incomplete: the default framebuffer doesn't exist",
+        FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "The framebuffer is incomplete: incomplete attachment",
+        FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "The framebuffer is incomplete: missing attachment",
+        FRAMEBUFFER_INCOMPLETE_DIMENSIONS => "The framebuffer is incomplete: attachments have different dimensions",
+        FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "The framebuffer is incomplete: a draw buffer has no attachment",
+        FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "The framebuffer is incomplete: the read buffer has no attachment",
+        FRAMEBUFFER_UNSUPPORTED => "The framebuffer is incomplete: unsupported combination of formats",
+        FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "The framebuffer is incomplete: incomplete multisample (mismatched sample counts)",
+        FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "The framebuffer is incomplete: incomplete layer targets",
+        0 => "Couldn't check the framebuffer status",
+        _ => "The framebuffer is incomplete: unknown status",
+    }
+}
@@ -1,6 +1,35 @@
+use gleam::gl;
+use std::cell::Cell;
+use std::ffi::CStr;
+use std::str;
+
+/// Whether a context is a GLES one, and its version in the same format
+/// as `GLFeatureRequirements`, as returned by `get_gl_version`.
+pub type GLVersion = (bool, u32);
+
+// The version of the context current in this thread, if known
+thread_local!(static CURRENT_GL_VERSION: Cell<Option<GLVersion>> = Cell::new(None));
+
+/// Tells the crate the version of the context current in this thread
+/// (or `None` if unknown), so `get_gl_version` doesn't need to query
+/// and parse it each time. `GLContext` calls it when it's made current.
+pub fn set_current_gl_version(version: Option<GLVersion>) {
+    CURRENT_GL_VERSION.with(|current| current.set(version));
+}
 
 pub enum GLFeature {
     FramebufferMultisample, // Framebuffer multisample, for antialising
+    BufferStorage, // Immutable (and persistently mappable) buffer storage
+    FramebufferBlit, // glBlitFramebuffer
+    InternalformatQuery, // glGetInternalformativ
+    MixedAttachmentSizes, // Framebuffer attachments with different sizes
+    PixelBufferMap, // Pixel pack buffers with glMapBufferRange
+    FramebufferNoAttachments, // Framebuffers with default parameters and no attachments
+    ImageLoadStore, // glBindImageTexture and image load/store in shaders
+    TextureStorage, // Immutable texture storage (glTexStorage2D)
+    FenceSync, // Sync objects (glFenceSync)
+    InvalidateFramebuffer, // glInvalidateFramebuffer and glInvalidateSubFramebuffer
+    PixelBufferObject, // Reads into GL_PIXEL_PACK_BUFFER
 }
 
 /// Possible feature requirements:
@@ -10,7 +39,7 @@ pub enum GLFeature {
 #[allow(dead_code)]
 pub struct GLFeatureRequirements {
     opengl_version: u32, // OpenGL version: 1.0 => 10, etc...
-    gles_version: u32,
+    gles_version: u32, // 0 if not available in GLES
     // Better not check for extensions unless necessary
     // They use different symbols, and that adds a lot of complexity
     // extensions: Vec<&'static str>
@@ -23,14 +52,148 @@ fn get_feature_requirements(feature: GLFeature) -> GLFeatureRequirements {
             opengl_version: 30,
             gles_version: 30,
             // extensions: vec!["GL_EXT_framebuffer_multisample"]
-        }
+        },
+        GLFeature::BufferStorage => GLFeatureRequirements {
+            opengl_version: 44,
+            gles_version: 0,
+            // extensions: vec!["GL_ARB_buffer_storage"]
+        },
+        GLFeature::FramebufferBlit => GLFeatureRequirements {
+            opengl_version: 30,
+            gles_version: 30,
+            // extensions: vec!["GL_EXT_framebuffer_blit"]
+        },
+        GLFeature::InternalformatQuery => GLFeatureRequirements {
+            opengl_version: 42,
+            gles_version: 30,
+            // extensions: vec!["GL_ARB_internalformat_query"]
+        },
+        // NOTE: GLES2 requires every attachment to have the same size
+        // (GL_FRAMEBUFFER_INCOMPLETE_DIMENSIONS)
+        GLFeature::MixedAttachmentSizes => GLFeatureRequirements {
+            opengl_version: 30,
+            gles_version: 30,
+            // extensions: vec!["GL_ARB_framebuffer_object"]
+        },
+        GLFeature::PixelBufferMap => GLFeatureRequirements {
+            opengl_version: 30,
+            gles_version: 30,
+            // extensions: vec!["GL_ARB_map_buffer_range"]
+        },
+        GLFeature::FramebufferNoAttachments => GLFeatureRequirements {
+            opengl_version: 43,
+            gles_version: 31,
+            // extensions: vec!["GL_ARB_framebuffer_no_attachments"]
+        },
+        GLFeature::ImageLoadStore => GLFeatureRequirements {
+            opengl_version: 42,
+            gles_version: 31,
+            // extensions: vec!["GL_ARB_shader_image_load_store"]
+        },
+        GLFeature::TextureStorage => GLFeatureRequirements {
+            opengl_version: 42,
+            gles_version: 30,
+            // extensions: vec!["GL_ARB_texture_storage"]
+        },
+        GLFeature::FenceSync => GLFeatureRequirements {
+            opengl_version: 32,
+            gles_version: 30,
+            // extensions: vec!["GL_ARB_sync"]
+        },
+        GLFeature::InvalidateFramebuffer => GLFeatureRequirements {
+            opengl_version: 43,
+            gles_version: 30,
+            // extensions: vec!["GL_ARB_invalidate_subdata"]
+        },
+        GLFeature::PixelBufferObject => GLFeatureRequirements {
+            opengl_version: 21,
+            gles_version: 30,
+            // extensions: vec!["GL_ARB_pixel_buffer_object", "GL_NV_pixel_buffer_object"]
+        },
+    }
+}
+
+// With the `gl` or `gles` features the API is known at compile time,
+// so the branches calling entry points of the other one are dead.
+#[cfg(feature="gles")]
+#[inline(always)]
+fn api_is_gles(_detected: bool) -> bool {
+    true
+}
+
+#[cfg(all(feature="gl", not(feature="gles")))]
+#[inline(always)]
+fn api_is_gles(_detected: bool) -> bool {
+    false
+}
+
+#[cfg(not(any(feature="gl", feature="gles")))]
+#[inline(always)]
+fn api_is_gles(detected: bool) -> bool {
+    detected
+}
+
+/// Returns whether the current context is a GLES one, and its
+/// version in the same format as `GLFeatureRequirements`.
+///
+/// With the `gles` (or `gl`) feature the context is always assumed
+/// to be GLES (or desktop GL).
+///
+/// The version string is "<major>.<minor><anything>" for OpenGL and
+/// "OpenGL ES <major>.<minor><anything>" for GLES. It's only queried
+/// if the current context isn't known (see `set_current_gl_version`).
+pub fn get_gl_version() -> GLVersion {
+    if let Some(version) = CURRENT_GL_VERSION.with(|current| current.get()) {
+        return version;
     }
+
+    query_gl_version()
+}
+
+/// Like `get_gl_version`, but always querying the current context.
+pub fn query_gl_version() -> GLVersion {
+    let version = unsafe {
+        let ptr = gl::GetString(gl::VERSION);
+        if ptr.is_null() {
+            return (false, 0);
+        }
+        CStr::from_ptr(ptr as *const _).to_bytes()
+    };
+
+    let version = str::from_utf8(version).unwrap_or("");
+
+    let (is_gles, version) = if version.starts_with("OpenGL ES ") {
+        (true, &version[10..])
+    } else {
+        (false, version)
+    };
+
+    let mut numbers = version.split(|c: char| !c.is_digit(10))
+                             .take(2)
+                             .map(|n| n.parse::<u32>().unwrap_or(0));
+
+    let major = numbers.next().unwrap_or(0);
+    let minor = numbers.next().unwrap_or(0);
+
+    (api_is_gles(is_gles), major * 10 + minor)
 }
 
 impl GLFeature {
+    // NOTE: Requires a current context
     pub fn is_supported(feature: GLFeature) -> bool {
-        let _ = get_feature_requirements(feature);
-        // TODO
-        false
+        GLFeature::is_supported_in(feature, get_gl_version())
+    }
+
+    /// Whether `feature` is available in a context of `version`
+    /// (see `GLContextCapabilities::gl_version`). This doesn't touch GL.
+    pub fn is_supported_in(feature: GLFeature, version: GLVersion) -> bool {
+        let requirements = get_feature_requirements(feature);
+        let (is_gles, version) = version;
+
+        if is_gles {
+            requirements.gles_version != 0 && version >= requirements.gles_version
+        } else {
+            version >= requirements.opengl_version
+        }
     }
 }
@@ -0,0 +1,62 @@
+use geom::Size2D;
+use std::i32;
+use GLContextError;
+use Result;
+
+/// Sizes accepted by the public constructors.
+///
+/// GL wants `GLsizei` (signed) dimensions, but pixel sizes are naturally
+/// unsigned, so we accept both `Size2D<i32>` and `Size2D<u32>`, and
+/// reject anything GL can't represent before reaching the driver.
+pub trait ToGLSize {
+    fn to_gl_size(&self) -> Result<Size2D<i32>>;
+}
+
+impl ToGLSize for Size2D<i32> {
+    fn to_gl_size(&self) -> Result<Size2D<i32>> {
+        if self.width < 0 || self.height < 0 {
+            return Err(GLContextError::InvalidRequest("Negative sizes are not allowed"));
+        }
+
+        Ok(*self)
+    }
+}
+
+impl ToGLSize for Size2D<u32> {
+    fn to_gl_size(&self) -> Result<Size2D<i32>> {
+        let max = i32::MAX as u32;
+
+        if self.width > max || self.height > max {
+            return Err(GLContextError::InvalidRequest("Size too big"));
+        }
+
+        Ok(Size2D(self.width as i32, self.height as i32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Size2D;
+    use std::i32;
+    use std::u32;
+
+    use GLContextError;
+    use super::ToGLSize;
+
+    #[test]
+    fn signed_sizes() {
+        assert_eq!(Size2D(0i32, 0i32).to_gl_size(), Ok(Size2D(0, 0)));
+        assert_eq!(Size2D(i32::MAX, 16).to_gl_size(), Ok(Size2D(i32::MAX, 16)));
+        assert_eq!(Size2D(-1i32, 16).to_gl_size(),
+                   Err(GLContextError::InvalidRequest("Negative sizes are not allowed")));
+        assert!(Size2D(16, i32::MIN).to_gl_size().is_err());
+    }
+
+    #[test]
+    fn unsigned_sizes() {
+        assert_eq!(Size2D(0u32, 0u32).to_gl_size(), Ok(Size2D(0, 0)));
+        assert_eq!(Size2D(i32::MAX as u32, 1u32).to_gl_size(), Ok(Size2D(i32::MAX, 1)));
+        assert!(Size2D(i32::MAX as u32 + 1, 1u32).to_gl_size().is_err());
+        assert!(Size2D(1u32, u32::MAX).to_gl_size().is_err());
+    }
+}
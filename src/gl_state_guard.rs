@@ -0,0 +1,141 @@
+use gleam::gl;
+use gleam::gl::types::{GLenum, GLint, GLsizei, GLuint};
+
+use gl_feature::get_gl_version;
+
+// NOTE: Not exposed by the GLES2 bindings
+const READ_FRAMEBUFFER: GLenum = 0x8CA8;
+const DRAW_FRAMEBUFFER: GLenum = 0x8CA9;
+const READ_FRAMEBUFFER_BINDING: GLenum = 0x8CAA;
+const DRAW_FRAMEBUFFER_BINDING: GLenum = 0x8CA6;
+const READ_BUFFER: GLenum = 0x0C02;
+const PACK_ROW_LENGTH: GLenum = 0x0D02;
+const DRAW_BUFFER0: GLenum = 0x8825;
+const MAX_DRAW_BUFFERS: GLenum = 0x8824;
+const PIXEL_PACK_BUFFER: GLenum = 0x88EB;
+const PIXEL_PACK_BUFFER_BINDING: GLenum = 0x88ED;
+
+/// Snapshots the GL state the crate's helpers modify, and restores
+/// it when dropped, so we behave well inside applications sharing
+/// the context with us.
+///
+/// The saved state is:
+///  * Framebuffer bindings (read and draw separately where available)
+///  * Renderbuffer binding
+///  * Read buffer (GL and GLES3) and every draw buffer (GL only) of those
+///    framebuffers
+///  * `GL_PACK_ALIGNMENT`, and `GL_PACK_ROW_LENGTH` where available
+///  * The `GL_PIXEL_PACK_BUFFER` binding (GL 2.1 and GLES3), which is
+///    reset to none while the guard lives, so reads into client memory
+///    aren't taken as buffer offsets
+///
+/// NOTE: Requires the context to be current both on creation and drop.
+pub struct GLStateGuard {
+    // Whether we have separate read/draw framebuffers (GL/GLES 3.0)
+    separate_framebuffers: bool,
+    is_gles: bool,
+    draw_framebuffer: GLuint,
+    read_framebuffer: GLuint,
+    renderbuffer: GLuint,
+    read_buffer: GLenum,
+    // GL_DRAW_BUFFERi, for applications using multiple render targets
+    draw_buffers: Vec<GLenum>,
+    pack_alignment: GLint,
+    // Only if not GLES2
+    pack_row_length: Option<GLint>,
+    // Only where pixel buffer objects exist
+    pixel_pack_buffer: Option<GLuint>,
+}
+
+fn get_integer(name: GLenum) -> GLint {
+    let mut value: GLint = 0;
+    unsafe {
+        gl::GetIntegerv(name, &mut value);
+    }
+    value
+}
+
+impl GLStateGuard {
+    pub fn new() -> GLStateGuard {
+        let (is_gles, version) = get_gl_version();
+        let separate_framebuffers = version >= 30;
+
+        let draw_framebuffer = get_integer(DRAW_FRAMEBUFFER_BINDING) as GLuint;
+        let read_framebuffer = if separate_framebuffers {
+            get_integer(READ_FRAMEBUFFER_BINDING) as GLuint
+        } else {
+            draw_framebuffer
+        };
+
+        let draw_buffers = if !is_gles {
+            let count = if version >= 20 { get_integer(MAX_DRAW_BUFFERS) } else { 1 };
+            (0..count as GLenum).map(|i| get_integer(DRAW_BUFFER0 + i) as GLenum).collect()
+        } else {
+            vec![]
+        };
+
+        let pixel_pack_buffer = if (!is_gles && version >= 21) || version >= 30 {
+            let buffer = get_integer(PIXEL_PACK_BUFFER_BINDING) as GLuint;
+            unsafe {
+                gl::BindBuffer(PIXEL_PACK_BUFFER, 0);
+            }
+            Some(buffer)
+        } else {
+            None
+        };
+
+        GLStateGuard {
+            separate_framebuffers: separate_framebuffers,
+            is_gles: is_gles,
+            draw_framebuffer: draw_framebuffer,
+            read_framebuffer: read_framebuffer,
+            renderbuffer: get_integer(gl::RENDERBUFFER_BINDING) as GLuint,
+            read_buffer: if separate_framebuffers { get_integer(READ_BUFFER) as GLenum } else { 0 },
+            draw_buffers: draw_buffers,
+            pack_alignment: get_integer(gl::PACK_ALIGNMENT),
+            pack_row_length: if !is_gles || version >= 30 {
+                Some(get_integer(PACK_ROW_LENGTH))
+            } else {
+                None
+            },
+            pixel_pack_buffer: pixel_pack_buffer,
+        }
+    }
+}
+
+impl Drop for GLStateGuard {
+    fn drop(&mut self) {
+        unsafe {
+            if self.separate_framebuffers {
+                gl::BindFramebuffer(READ_FRAMEBUFFER, self.read_framebuffer);
+                gl::ReadBuffer(self.read_buffer);
+                gl::BindFramebuffer(DRAW_FRAMEBUFFER, self.draw_framebuffer);
+            } else {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, self.draw_framebuffer);
+            }
+
+            // Trailing GL_NONEs are the default, skip them. A single buffer
+            // goes through glDrawBuffer, since glDrawBuffers rejects GL_BACK
+            // on the default framebuffer.
+            let count = self.draw_buffers.iter()
+                                         .rposition(|&buffer| buffer != gl::NONE)
+                                         .map_or(1, |last| last + 1);
+            if count == 1 && !self.draw_buffers.is_empty() {
+                gl::DrawBuffer(self.draw_buffers[0]);
+            } else if count > 1 {
+                gl::DrawBuffers(count as GLsizei, self.draw_buffers.as_ptr());
+            }
+
+            if let Some(buffer) = self.pixel_pack_buffer {
+                gl::BindBuffer(PIXEL_PACK_BUFFER, buffer);
+            }
+
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.renderbuffer);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, self.pack_alignment);
+
+            if let Some(row_length) = self.pack_row_length {
+                gl::PixelStorei(PACK_ROW_LENGTH, row_length);
+            }
+        }
+    }
+}
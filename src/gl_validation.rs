@@ -0,0 +1,178 @@
+use geom::Size2D;
+use std::isize;
+
+use GLContextAttributes;
+use GLContextCapabilities;
+use AntialiasPolicy;
+use GLFeature;
+use GLContextError;
+use Result;
+
+/// Validates a draw buffer request before any GL object is created,
+/// so the allocation path can assume the request is sane.
+///
+/// This checks the size against the implementation limits (and that
+/// a full RGBA readback of it is addressable), the amount of samples,
+/// the alpha and stencil bits, and the amount of color attachments.
+///
+/// It never panics, whatever the input, and doesn't touch GL: the
+/// version is taken from `capabilities`, so it works without a current
+/// context (as with `DrawBuffer::new_lazy`).
+///
+/// The attachment-related attributes are ignored for framebuffers
+/// without attachments.
+pub fn validate_request(size: &Size2D<i32>,
+                        attrs: &GLContextAttributes,
+                        capabilities: &GLContextCapabilities) -> Result<()> {
+    if size.width < 0 || size.height < 0 {
+        return Err(GLContextError::InvalidRequest("Negative sizes are not allowed"));
+    }
+
+    try!(capabilities.check_size(size));
+
+    let bytes = (size.width as usize).checked_mul(size.height as usize)
+                                     .and_then(|pixels| pixels.checked_mul(4));
+    match bytes {
+        Some(bytes) if bytes <= isize::MAX as usize => {},
+        _ => return Err(GLContextError::InvalidRequest("Size too big")),
+    }
+
+    if attrs.no_attachments {
+        if !GLFeature::is_supported_in(GLFeature::FramebufferNoAttachments, capabilities.gl_version) {
+            return Err(GLContextError::Unsupported("Framebuffers without attachments require GL 4.3 or GLES 3.1"));
+        }
+    } else {
+        if attrs.color_attachments < 1 {
+            return Err(GLContextError::InvalidRequest("At least one color attachment is required"));
+        }
+
+        if attrs.color_attachments > capabilities.max_render_targets() {
+            return Err(GLContextError::InvalidRequest("Requested more color attachments than GL_MAX_COLOR_ATTACHMENTS/GL_MAX_DRAW_BUFFERS allow"));
+        }
+
+        if attrs.alpha && attrs.min_alpha_bits > 8 {
+            return Err(GLContextError::Unsupported("No supported color format has more than 8 alpha bits"));
+        }
+
+        if attrs.stencil && attrs.stencil_bits > 16 {
+            return Err(GLContextError::Unsupported("No supported stencil format has more than 16 bits"));
+        }
+    }
+
+    // The quality is clamped to the implementation limits, so it
+    // doesn't need validation
+    if attrs.antialias && attrs.antialias_quality.is_none() {
+        if attrs.samples < 0 {
+            return Err(GLContextError::InvalidRequest("Negative amount of samples"));
+        }
+
+        if attrs.samples > capabilities.max_samples && attrs.antialias_policy == AntialiasPolicy::Strict {
+            if capabilities.max_samples == 0 {
+                return Err(GLContextError::Unsupported("The given GLContext doesn't support requested antialising"));
+            }
+
+            return Err(GLContextError::Unsupported("The given GLContext doesn't support the requested amount of samples"));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Size2D;
+    use std::i32;
+    use std::isize;
+
+    use AntialiasPolicy;
+    use GLContextAttributes;
+    use GLContextCapabilities;
+    use GLContextError;
+    use GLExtensions;
+    use super::validate_request;
+
+    fn capabilities() -> GLContextCapabilities {
+        GLContextCapabilities {
+            max_samples: 4,
+            max_renderbuffer_size: 4096,
+            max_color_attachments: 4,
+            max_draw_buffers: 8,
+            extensions: GLExtensions::empty(),
+            gl_version: (false, 45),
+        }
+    }
+
+    #[test]
+    fn color_attachments_are_limited_by_the_render_targets() {
+        let mut attrs = GLContextAttributes::default();
+        let size = Size2D(16, 16);
+
+        attrs.color_attachments = 4;
+        assert_eq!(validate_request(&size, &attrs, &capabilities()), Ok(()));
+
+        attrs.color_attachments = 9;
+        match validate_request(&size, &attrs, &capabilities()) {
+            Err(GLContextError::InvalidRequest(_)) => {},
+            result => panic!("Expected InvalidRequest, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn edge_cases_are_rejected_without_panicking() {
+        let dimensions = [i32::MIN, -1, 0, 1, 4096, 4097, i32::MAX];
+
+        // Also without a renderbuffer size limit, to reach the
+        // readback overflow check
+        let mut unlimited = capabilities();
+        unlimited.max_renderbuffer_size = i32::MAX;
+
+        for capabilities in [capabilities(), unlimited].iter() {
+            for &width in dimensions.iter() {
+                for &height in dimensions.iter() {
+                    for &samples in [-1, 0, 4, 5, i32::MAX].iter() {
+                        let mut attrs = GLContextAttributes::default();
+                        attrs.antialias = true;
+                        attrs.samples = samples;
+                        attrs.antialias_policy = AntialiasPolicy::Strict;
+
+                        let size = Size2D(width, height);
+                        let result = validate_request(&size, &attrs, capabilities);
+
+                        let invalid = width < 0 || height < 0 ||
+                                      width > capabilities.max_renderbuffer_size ||
+                                      height > capabilities.max_renderbuffer_size ||
+                                      (width as u64) * (height as u64) * 4 > isize::MAX as u64 ||
+                                      samples < 0 || samples > capabilities.max_samples;
+                        assert_eq!(result.is_err(), invalid,
+                                   "Unexpected result for {:?} with {} samples: {:?}",
+                                   size, samples, result);
+                    }
+                }
+            }
+        }
+
+        let mut attrs = GLContextAttributes::default();
+        for &color_attachments in [i32::MIN, -1, 0, i32::MAX].iter() {
+            attrs.color_attachments = color_attachments;
+            assert!(validate_request(&Size2D(1, 1), &attrs, &capabilities()).is_err());
+        }
+    }
+
+    #[test]
+    fn no_attachments_depend_on_the_capabilities_version() {
+        let mut attrs = GLContextAttributes::default();
+        attrs.no_attachments = true;
+
+        let mut capabilities = capabilities();
+        assert_eq!(validate_request(&Size2D(16, 16), &attrs, &capabilities), Ok(()));
+
+        capabilities.gl_version = (false, 42);
+        match validate_request(&Size2D(16, 16), &attrs, &capabilities) {
+            Err(GLContextError::Unsupported(_)) => {},
+            result => panic!("Expected Unsupported, got {:?}", result),
+        }
+
+        capabilities.gl_version = (true, 31);
+        assert_eq!(validate_request(&Size2D(16, 16), &attrs, &capabilities), Ok(()));
+    }
+}
@@ -0,0 +1,110 @@
+use geom::Size2D;
+use std::sync::mpsc::{channel, Sender};
+use std::thread::{self, JoinHandle};
+
+use GLContext;
+use GLContextAttributes;
+use GLContextError;
+use Result;
+
+type Job = Box<FnMut(&mut GLContext) + Send>;
+
+/// A thread owning a `GLContext`, which is created there (so creation,
+/// including fallback retries, doesn't block the caller) and stays
+/// current there. Work is sent to it as closures.
+///
+/// Dropping the worker waits for the pending jobs, and destroys the
+/// context.
+pub struct GLWorker {
+    sender: Option<Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl GLWorker {
+    /// Creates the context on a new thread, blocking until it's done.
+    pub fn new(size: Size2D<i32>, attributes: GLContextAttributes)
+        -> Result<GLWorker> {
+        let (result_sender, result_receiver) = channel();
+
+        let worker = GLWorker::spawn(size, attributes, move |result| {
+            let _ = result_sender.send(result.map(|_| ()));
+        });
+
+        match result_receiver.recv() {
+            Ok(Ok(())) => Ok(worker),
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(GLContextError::NativeContext("The worker thread panicked while creating the context")),
+        }
+    }
+
+    /// Creates the context on a new thread, returning immediately.
+    /// `callback` is called on that thread once creation finishes.
+    ///
+    /// The context is created with `GLContext::new_with_fallback`.
+    /// Jobs sent before creation finishes are queued, and dropped
+    /// without running if it fails.
+    pub fn spawn<F>(size: Size2D<i32>, attributes: GLContextAttributes, callback: F) -> GLWorker
+        where F: FnOnce(Result<&mut GLContext>) + Send + 'static {
+        let (sender, receiver) = channel::<Job>();
+
+        let thread = thread::spawn(move || {
+            let mut context = match GLContext::new_with_fallback(size, attributes) {
+                Ok(context) => context,
+                Err(err) => {
+                    callback(Err(err));
+                    return;
+                }
+            };
+
+            callback(Ok(&mut context));
+
+            for mut job in receiver.iter() {
+                job(&mut context);
+            }
+        });
+
+        GLWorker {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    /// Runs `job` on the worker thread, without waiting for it.
+    pub fn execute<F>(&self, job: F) -> Result<()>
+        where F: FnMut(&mut GLContext) + Send + 'static {
+        match self.sender.as_ref().unwrap().send(Box::new(job)) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(GLContextError::InvalidOperation("The worker thread has exited")),
+        }
+    }
+
+    /// Runs `job` on the worker thread, and waits for its result.
+    pub fn execute_sync<F, R>(&self, job: F) -> Result<R>
+        where F: FnOnce(&mut GLContext) -> R + Send + 'static,
+              R: Send + 'static {
+        let (result_sender, result_receiver) = channel();
+        let mut job = Some(job);
+
+        try!(self.execute(move |context| {
+            if let Some(job) = job.take() {
+                let _ = result_sender.send(job(context));
+            }
+        }));
+
+        match result_receiver.recv() {
+            Ok(result) => Ok(result),
+            Err(_) => Err(GLContextError::InvalidOperation("The worker thread has exited")),
+        }
+    }
+}
+
+impl Drop for GLWorker {
+    fn drop(&mut self) {
+        // Closing the channel ends the job loop
+        drop(self.sender.take());
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -1,3 +1,7 @@
+#[macro_use]
+extern crate bitflags;
+#[macro_use]
+extern crate log;
 extern crate gleam;
 extern crate libc;
 extern crate geom;
@@ -10,19 +14,62 @@ extern crate glx;
 extern crate cgl;
 
 mod platform;
-pub use platform::{NativeGLContext, NativeGLContextMethods};
+pub use platform::{NativeGLContext, NativeGLContextMethods, BackendKind};
+
+mod backend_info;
+pub use backend_info::BackendInfo;
 
 mod gl_context;
-pub use gl_context::GLContext;
+pub use gl_context::{GLContext, notify_external_make_current};
+
+mod gl_worker;
+pub use gl_worker::GLWorker;
 
 mod draw_buffer;
-pub use draw_buffer::DrawBuffer;
+pub use draw_buffer::{DrawBuffer, DrawBufferBuilder, CompositeOp, ExportedColorBuffer, Handoff};
 
 mod gl_context_attributes;
-pub use gl_context_attributes::GLContextAttributes;
+pub use gl_context_attributes::{GLContextAttributes, AntialiasPolicy, AntialiasQuality, TextureParameters};
 
 mod gl_context_capabilities;
-pub use gl_context_capabilities::GLContextCapabilities;
+pub use gl_context_capabilities::{GLContextCapabilities, GLExtensions};
+pub use gl_context_capabilities::{EXT_PACKED_DEPTH_STENCIL, EXT_FLOAT_COLOR, EXT_SRGB};
+pub use gl_context_capabilities::{EXT_MULTISAMPLE, EXT_TIMER_QUERY, EXT_BGRA_READ, EXT_MULTIVIEW, EXT_BGRA8_FORMAT};
+
+mod gl_size;
+pub use gl_size::ToGLSize;
+
+mod gl_state_guard;
+pub use gl_state_guard::GLStateGuard;
+
+mod gl_errors;
+pub use gl_errors::{set_strict_errors, describe_framebuffer_status};
+
+mod gl_context_error;
+pub use gl_context_error::{GLContextError, Result};
+
+mod pending_deletions;
+
+mod quad_blit;
+
+mod gl_validation;
+pub use gl_validation::validate_request;
 
 mod gl_feature;
-pub use gl_feature::GLFeature;
+pub use gl_feature::{GLFeature, GLVersion};
+
+pub mod pixels;
+
+pub mod formats;
+
+mod snapshot;
+pub use snapshot::{Snapshot, CapturedFrame};
+
+mod persistent_readback;
+pub use persistent_readback::PersistentReadback;
+
+mod mapped_readback;
+pub use mapped_readback::MappedReadback;
+
+mod fence;
+pub use fence::Fence;
@@ -0,0 +1,107 @@
+use geom::Size2D;
+use gleam::gl;
+use gleam::gl::types::{GLuint, GLbitfield, GLsizeiptr};
+use std::ops::Deref;
+use std::ptr;
+use std::slice;
+
+use GLFeature;
+use Result;
+use gl_errors::check_gl_error;
+
+// NOTE: Not exposed by the GLES bindings
+const MAP_READ_BIT: GLbitfield = 0x0001;
+
+enum MappedData {
+    // A pixel pack buffer mapped for reading
+    Mapped(GLuint, *const u8, usize),
+    // A plain copy, used when mapping isn't available
+    Copied(Vec<u8>),
+}
+
+/// The result of `DrawBuffer::read_pixels_mapped`: RGBA data which,
+/// when possible, points directly to a mapped pixel pack buffer,
+/// avoiding a copy.
+///
+/// NOTE: As with `DrawBuffer`, the context used to create it
+/// must be current when it's dropped, since that unmaps the buffer.
+pub struct MappedReadback {
+    data: MappedData,
+}
+
+impl MappedReadback {
+    /// Whether the data is mapped, or had to be copied.
+    pub fn is_mapped(&self) -> bool {
+        match self.data {
+            MappedData::Mapped(..) => true,
+            MappedData::Copied(_) => false,
+        }
+    }
+}
+
+impl Deref for MappedReadback {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self.data {
+            MappedData::Mapped(_, mapping, len) => unsafe { slice::from_raw_parts(mapping, len) },
+            MappedData::Copied(ref pixels) => pixels,
+        }
+    }
+}
+
+impl Drop for MappedReadback {
+    fn drop(&mut self) {
+        if let MappedData::Mapped(buffer, _, _) = self.data {
+            unsafe {
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+                gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+                gl::DeleteBuffers(1, &buffer);
+            }
+        }
+    }
+}
+
+pub fn from_vec(pixels: Vec<u8>) -> MappedReadback {
+    MappedReadback {
+        data: MappedData::Copied(pixels),
+    }
+}
+
+/// Reads the currently bound read framebuffer into a new pixel pack
+/// buffer and maps it. Returns `None` if mapping isn't available, so
+/// the caller can fall back to a copy.
+pub fn read_and_map(size: &Size2D<i32>) -> Result<Option<MappedReadback>> {
+    if !GLFeature::is_supported(GLFeature::PixelBufferMap) {
+        return Ok(None);
+    }
+
+    let len = size.width as usize * size.height as usize * 4;
+    let mut buffer: GLuint = 0;
+
+    let mapping = unsafe {
+        gl::GenBuffers(1, &mut buffer);
+        gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
+        gl::BufferData(gl::PIXEL_PACK_BUFFER, len as GLsizeiptr, ptr::null(), gl::STREAM_READ);
+        gl::ReadPixels(0, 0, size.width, size.height,
+                       gl::RGBA, gl::UNSIGNED_BYTE, 0 as *mut _);
+        let mapping = gl::MapBufferRange(gl::PIXEL_PACK_BUFFER, 0, len as GLsizeiptr, MAP_READ_BIT);
+        gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        mapping
+    };
+
+    if mapping.is_null() {
+        unsafe { gl::DeleteBuffers(1, &buffer) };
+        return Ok(None);
+    }
+
+    let readback = MappedReadback {
+        data: MappedData::Mapped(buffer, mapping as *const u8, len),
+    };
+
+    // Dropping `readback` unmaps and deletes the buffer
+    try!(check_gl_error("glReadPixels"));
+
+    Ok(Some(readback))
+}
@@ -0,0 +1,123 @@
+use gleam::gl;
+use gleam::gl::types::{GLuint, GLsizei};
+
+/// GL objects whose owner was dropped while their context wasn't
+/// current. They're deleted the next time the context is made
+/// current through `GLContext::make_current`.
+pub struct PendingDeletions {
+    pub framebuffers: Vec<GLuint>,
+    pub render_buffers: Vec<GLuint>,
+    pub textures: Vec<GLuint>,
+    // Set when the owning context is dropped, so owners dropped
+    // afterwards know their objects are already gone
+    context_destroyed: bool,
+}
+
+impl PendingDeletions {
+    pub fn new() -> PendingDeletions {
+        PendingDeletions {
+            framebuffers: vec![],
+            render_buffers: vec![],
+            textures: vec![],
+            context_destroyed: false,
+        }
+    }
+
+    /// Records that the owning context was destroyed, discarding the
+    /// queued objects (they were destroyed along with it).
+    pub fn mark_context_destroyed(&mut self) {
+        self.framebuffers.clear();
+        self.render_buffers.clear();
+        self.textures.clear();
+        self.context_destroyed = true;
+    }
+
+    /// Whether the owning context was destroyed. Objects of owners
+    /// outliving it must not be deleted (nor queued) anymore.
+    pub fn is_context_destroyed(&self) -> bool {
+        self.context_destroyed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.framebuffers.is_empty() &&
+        self.render_buffers.is_empty() &&
+        self.textures.is_empty()
+    }
+
+    /// Moves every object of `other` to this queue.
+    pub fn append(&mut self, other: &mut PendingDeletions) {
+        self.framebuffers.extend(other.framebuffers.drain(..));
+        self.render_buffers.extend(other.render_buffers.drain(..));
+        self.textures.extend(other.textures.drain(..));
+    }
+
+    /// Deletes every queued object.
+    ///
+    /// NOTE: Requires the owning context to be current.
+    pub fn flush(&mut self) {
+        // NOTE: glDelete* silently ignore zeros
+        unsafe {
+            gl::DeleteFramebuffers(self.framebuffers.len() as GLsizei, self.framebuffers.as_ptr());
+            gl::DeleteRenderbuffers(self.render_buffers.len() as GLsizei, self.render_buffers.as_ptr());
+            gl::DeleteTextures(self.textures.len() as GLsizei, self.textures.as_ptr());
+        }
+
+        self.framebuffers.clear();
+        self.render_buffers.clear();
+        self.textures.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PendingDeletions;
+
+    fn queue(base: u32) -> PendingDeletions {
+        let mut pending = PendingDeletions::new();
+        pending.framebuffers.push(base);
+        pending.render_buffers.push(base + 1);
+        pending.render_buffers.push(base + 2);
+        pending.textures.push(base + 3);
+        pending
+    }
+
+    #[test]
+    fn new_queues_are_empty() {
+        let pending = PendingDeletions::new();
+        assert!(pending.is_empty());
+        assert!(!pending.is_context_destroyed());
+    }
+
+    #[test]
+    fn any_object_makes_the_queue_non_empty() {
+        let mut pending = PendingDeletions::new();
+        pending.textures.push(1);
+        assert!(!pending.is_empty());
+    }
+
+    #[test]
+    fn append_drains_the_other_queue() {
+        let mut pending = queue(10);
+        let mut other = queue(20);
+
+        pending.append(&mut other);
+
+        assert!(other.is_empty());
+        assert_eq!(pending.framebuffers, vec![10, 20]);
+        assert_eq!(pending.render_buffers, vec![11, 12, 21, 22]);
+        assert_eq!(pending.textures, vec![13, 23]);
+    }
+
+    #[test]
+    fn marking_the_context_destroyed_clears_every_queue() {
+        let mut pending = queue(10);
+
+        pending.mark_context_destroyed();
+
+        assert!(pending.is_context_destroyed());
+        assert!(pending.is_empty());
+        assert!(pending.framebuffers.is_empty());
+        assert!(pending.render_buffers.is_empty());
+        assert!(pending.textures.is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use geom::Size2D;
+use gleam::gl;
+use gleam::gl::types::{GLuint, GLbitfield, GLsizeiptr};
+use std::isize;
+use std::ptr;
+use std::slice;
+
+use Fence;
+use GLFeature;
+use GLContextError;
+use Result;
+
+// NOTE: GL 4.4 only, not exposed by the GLES bindings
+const MAP_READ_BIT: GLbitfield = 0x0001;
+const MAP_PERSISTENT_BIT: GLbitfield = 0x0040;
+const MAP_COHERENT_BIT: GLbitfield = 0x0080;
+
+// How long `wait` blocks before giving up: five seconds, in nanoseconds
+const WAIT_TIMEOUT: u64 = 5000000000;
+
+/// A pixel pack buffer that stays persistently (and coherently) mapped,
+/// so `DrawBuffer::read_into_persistent` results can be read by the CPU
+/// without explicit map/unmap calls.
+///
+/// Requires GL 4.4 (`GL_ARB_buffer_storage`).
+///
+/// NOTE: As with `DrawBuffer`, the context used to create it
+/// must be current when it's dropped.
+pub struct PersistentReadback {
+    buffer: GLuint,
+    size: Size2D<i32>,
+    mapping: *const u8,
+    fence: Option<Fence>,
+}
+
+impl PersistentReadback {
+    /// Creates a readback buffer big enough to hold an RGBA
+    /// image of `size`. Requires a current context.
+    pub fn new(size: Size2D<i32>) -> Result<PersistentReadback> {
+        if !GLFeature::is_supported(GLFeature::BufferStorage) {
+            return Err(GLContextError::Unsupported("Persistent mapping requires GL 4.4"));
+        }
+
+        if size.width <= 0 || size.height <= 0 {
+            return Err(GLContextError::InvalidRequest("Invalid readback size"));
+        }
+
+        let flags = MAP_READ_BIT | MAP_PERSISTENT_BIT | MAP_COHERENT_BIT;
+        let len = match byte_len(&size) {
+            Some(len) if len <= isize::MAX as usize => len as GLsizeiptr,
+            _ => return Err(GLContextError::InvalidRequest("The readback size is too big")),
+        };
+
+        let mut buffer: GLuint = 0;
+
+        let mapping = unsafe {
+            gl::GenBuffers(1, &mut buffer);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
+            gl::BufferStorage(gl::PIXEL_PACK_BUFFER, len, ptr::null(), flags);
+            let mapping = gl::MapBufferRange(gl::PIXEL_PACK_BUFFER, 0, len, flags);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            mapping
+        };
+
+        if mapping.is_null() {
+            unsafe { gl::DeleteBuffers(1, &buffer) };
+            return Err(GLContextError::GLError("glMapBufferRange"));
+        }
+
+        Ok(PersistentReadback {
+            buffer: buffer,
+            size: size,
+            mapping: mapping as *const u8,
+            fence: None,
+        })
+    }
+
+    #[inline(always)]
+    pub fn get_buffer(&self) -> GLuint {
+        self.buffer
+    }
+
+    #[inline(always)]
+    pub fn get_size(&self) -> Size2D<i32> {
+        self.size
+    }
+
+    /// Inserts a fence after the commands issued so far, and flushes
+    /// them so the fence can signal even if `wait` is called from a
+    /// context other than the one that inserted it.
+    /// `wait` will block until it's signaled.
+    pub fn insert_fence(&mut self) -> Result<()> {
+        // NOTE: Drop the previous fence first, so a failure doesn't
+        // leave a stale one behind
+        self.fence = None;
+        self.fence = Some(try!(Fence::insert()));
+        Ok(())
+    }
+
+    /// Waits for the last read to complete, and returns the mapped
+    /// RGBA data (bottom row first).
+    ///
+    /// Gives up with an error if the read doesn't complete in a few
+    /// seconds. Use `wait_timeout` to choose how long to wait.
+    pub fn wait(&mut self) -> Result<&[u8]> {
+        match try!(self.wait_timeout(WAIT_TIMEOUT)) {
+            Some(pixels) => Ok(pixels),
+            None => Err(GLContextError::GLError("Timed out waiting for the read")),
+        }
+    }
+
+    /// Like `wait`, but blocks for at most `timeout` nanoseconds,
+    /// returning `None` if the read didn't complete in time. It can be
+    /// waited for again afterwards.
+    pub fn wait_timeout(&mut self, timeout: u64) -> Result<Option<&[u8]>> {
+        let signaled = match self.fence {
+            Some(ref fence) => try!(fence.client_wait(timeout)),
+            None => return Err(GLContextError::InvalidOperation("No read was issued")),
+        };
+
+        if !signaled {
+            return Ok(None);
+        }
+
+        self.fence = None;
+
+        // NOTE: Checked on creation
+        let len = byte_len(&self.size).unwrap();
+
+        Ok(Some(unsafe { slice::from_raw_parts(self.mapping, len) }))
+    }
+}
+
+// The length in bytes of an RGBA image of `size`, if it fits in usize
+fn byte_len(size: &Size2D<i32>) -> Option<usize> {
+    (size.width as usize).checked_mul(size.height as usize)
+                         .and_then(|len| len.checked_mul(4))
+}
+
+impl Drop for PersistentReadback {
+    fn drop(&mut self) {
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.buffer);
+            gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl::DeleteBuffers(1, &self.buffer);
+        }
+    }
+}
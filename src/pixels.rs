@@ -0,0 +1,274 @@
+//! Byte shuffling for readback data.
+//!
+//! Every conversion here works on tightly packed rows, which is what
+//! `DrawBuffer`'s readback methods return.
+//!
+//! The loops are written so the compiler can vectorize them: fixed-size
+//! chunks, no branches and no divisions per pixel. The exception is
+//! `unpremultiply`, which needs a division by alpha.
+
+use geom::Size2D;
+use std::mem;
+
+/// Channel order of the pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelOrder {
+    RGBA,
+    BGRA,
+    RGB,
+}
+
+impl ChannelOrder {
+    #[inline(always)]
+    pub fn bytes_per_pixel(&self) -> usize {
+        match *self {
+            ChannelOrder::RGBA | ChannelOrder::BGRA => 4,
+            ChannelOrder::RGB => 3,
+        }
+    }
+}
+
+/// Memory layout of an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelLayout {
+    pub channels: ChannelOrder,
+    /// Whether color channels are multiplied by alpha
+    pub premultiplied: bool,
+    /// Whether the first row is the top one. GL returns the bottom one first.
+    pub top_down: bool,
+}
+
+impl PixelLayout {
+    /// The layout `glReadPixels` returns with `GL_RGBA`.
+    pub fn gl_rgba(premultiplied: bool) -> PixelLayout {
+        PixelLayout {
+            channels: ChannelOrder::RGBA,
+            premultiplied: premultiplied,
+            top_down: false,
+        }
+    }
+}
+
+/// How the alpha channel of some pixels must be interpreted
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// Color channels are already multiplied by alpha
+    Premultiplied,
+    /// Color channels are independent of alpha
+    Straight,
+    /// There's no meaningful alpha: every pixel is fully opaque
+    Opaque,
+}
+
+/// Flips vertically an image with rows of `stride` bytes, in place.
+pub fn flip_rows(pixels: &mut [u8], stride: usize) {
+    if stride == 0 {
+        return;
+    }
+
+    let rows = pixels.len() / stride;
+    for row in 0..(rows / 2) {
+        let (top, bottom) = pixels.split_at_mut((rows - row - 1) * stride);
+        let top = &mut top[row * stride..(row + 1) * stride];
+        let bottom = &mut bottom[..stride];
+
+        for (a, b) in top.iter_mut().zip(bottom.iter_mut()) {
+            mem::swap(a, b);
+        }
+    }
+}
+
+// `value * alpha / 255`, rounded, without a division. This is exact
+// for any pair of bytes.
+#[inline(always)]
+fn multiply_alpha(value: u8, alpha: u32) -> u8 {
+    let product = value as u32 * alpha + 128;
+    ((product + (product >> 8)) >> 8) as u8
+}
+
+/// Multiplies the color channels of RGBA (or BGRA) pixels by alpha.
+pub fn premultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_mut(4) {
+        let alpha = pixel[3] as u32;
+        pixel[0] = multiply_alpha(pixel[0], alpha);
+        pixel[1] = multiply_alpha(pixel[1], alpha);
+        pixel[2] = multiply_alpha(pixel[2], alpha);
+    }
+}
+
+/// Divides the color channels of RGBA (or BGRA) pixels by alpha.
+/// Fully transparent pixels become transparent black.
+pub fn unpremultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_mut(4) {
+        let alpha = pixel[3] as u32;
+        if alpha == 0 {
+            pixel[0] = 0;
+            pixel[1] = 0;
+            pixel[2] = 0;
+            continue;
+        }
+
+        pixel[0] = ((pixel[0] as u32 * 255 + alpha / 2) / alpha) as u8;
+        pixel[1] = ((pixel[1] as u32 * 255 + alpha / 2) / alpha) as u8;
+        pixel[2] = ((pixel[2] as u32 * 255 + alpha / 2) / alpha) as u8;
+    }
+}
+
+/// Swaps the red and blue channels of four-channel pixels,
+/// converting RGBA into BGRA and vice versa.
+pub fn swap_red_blue(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// Converts `pixels`, an image of `size` in the `from` layout,
+/// into the `to` layout.
+///
+/// When dropping the alpha channel (converting to `RGB`), colors are
+/// still converted according to `premultiplied`, so premultiplied RGB
+/// keeps the darkened colors and straight RGB doesn't.
+pub fn convert(pixels: &[u8], from: PixelLayout, to: PixelLayout, size: Size2D<i32>) -> Vec<u8> {
+    let pixel_count = size.width as usize * size.height as usize;
+    debug_assert!(pixels.len() >= pixel_count * from.channels.bytes_per_pixel());
+
+    // First expand everything into RGBA, which is where we do
+    // the alpha conversions.
+    let mut rgba = match from.channels {
+        ChannelOrder::RGBA => pixels[..pixel_count * 4].to_vec(),
+        ChannelOrder::BGRA => {
+            let mut rgba = pixels[..pixel_count * 4].to_vec();
+            swap_red_blue(&mut rgba);
+            rgba
+        }
+        ChannelOrder::RGB => {
+            let mut rgba = vec![255; pixel_count * 4];
+            for (rgba, rgb) in rgba.chunks_mut(4).zip(pixels[..pixel_count * 3].chunks(3)) {
+                rgba[0] = rgb[0];
+                rgba[1] = rgb[1];
+                rgba[2] = rgb[2];
+            }
+            rgba
+        }
+    };
+
+    // Opaque pixels are the same premultiplied or not
+    if from.channels != ChannelOrder::RGB {
+        if from.premultiplied && !to.premultiplied {
+            unpremultiply(&mut rgba);
+        } else if !from.premultiplied && to.premultiplied {
+            premultiply(&mut rgba);
+        }
+    }
+
+    let mut result = match to.channels {
+        ChannelOrder::RGBA => rgba,
+        ChannelOrder::BGRA => {
+            swap_red_blue(&mut rgba);
+            rgba
+        }
+        ChannelOrder::RGB => {
+            let mut rgb = vec![0; pixel_count * 3];
+            for (rgb, rgba) in rgb.chunks_mut(3).zip(rgba.chunks(4)) {
+                rgb[0] = rgba[0];
+                rgb[1] = rgba[1];
+                rgb[2] = rgba[2];
+            }
+            rgb
+        }
+    };
+
+    if from.top_down != to.top_down {
+        let stride = size.width as usize * to.channels.bytes_per_pixel();
+        flip_rows(&mut result, stride);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Size2D;
+
+    use super::{ChannelOrder, PixelLayout};
+    use super::{convert, flip_rows, premultiply, swap_red_blue, unpremultiply};
+
+    fn layout(channels: ChannelOrder, premultiplied: bool, top_down: bool) -> PixelLayout {
+        PixelLayout {
+            channels: channels,
+            premultiplied: premultiplied,
+            top_down: top_down,
+        }
+    }
+
+    #[test]
+    fn premultiply_round_trips_at_full_and_no_alpha() {
+        let original = vec![10, 128, 255, 255, 0, 0, 0, 0];
+
+        let mut pixels = original.clone();
+        premultiply(&mut pixels);
+        assert_eq!(pixels, original);
+        unpremultiply(&mut pixels);
+        assert_eq!(pixels, original);
+
+        // Transparent colors are lost, and become transparent black
+        let mut pixels = vec![10, 128, 255, 0];
+        premultiply(&mut pixels);
+        assert_eq!(pixels, vec![0, 0, 0, 0]);
+        let mut pixels = vec![10, 128, 255, 0];
+        unpremultiply(&mut pixels);
+        assert_eq!(pixels, vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn premultiply_rounds() {
+        let mut pixels = vec![255, 128, 1, 128];
+        premultiply(&mut pixels);
+        assert_eq!(pixels, vec![128, 64, 1, 128]);
+    }
+
+    #[test]
+    fn bgra_and_rgba_round_trip() {
+        let rgba = vec![1, 2, 3, 4, 5, 6, 7, 8];
+
+        let mut pixels = rgba.clone();
+        swap_red_blue(&mut pixels);
+        assert_eq!(pixels, vec![3, 2, 1, 4, 7, 6, 5, 8]);
+
+        let size = Size2D(2, 1);
+        let bgra = convert(&rgba, layout(ChannelOrder::RGBA, true, false),
+                           layout(ChannelOrder::BGRA, true, false), size);
+        assert_eq!(bgra, pixels);
+        assert_eq!(convert(&bgra, layout(ChannelOrder::BGRA, true, false),
+                           layout(ChannelOrder::RGBA, true, false), size), rgba);
+    }
+
+    #[test]
+    fn rgb_and_rgba_round_trip() {
+        let rgb = vec![1, 2, 3, 4, 5, 6];
+        let size = Size2D(2, 1);
+
+        let rgba = convert(&rgb, layout(ChannelOrder::RGB, false, false),
+                           layout(ChannelOrder::RGBA, false, false), size);
+        assert_eq!(rgba, vec![1, 2, 3, 255, 4, 5, 6, 255]);
+        assert_eq!(convert(&rgba, layout(ChannelOrder::RGBA, false, false),
+                           layout(ChannelOrder::RGB, false, false), size), rgb);
+    }
+
+    #[test]
+    fn flip_rows_with_odd_heights() {
+        let mut pixels = vec![1, 1, 2, 2, 3, 3];
+        flip_rows(&mut pixels, 2);
+        assert_eq!(pixels, vec![3, 3, 2, 2, 1, 1]);
+
+        let mut pixels = vec![1, 2, 3];
+        flip_rows(&mut pixels, 3);
+        assert_eq!(pixels, vec![1, 2, 3]);
+
+        // Converting between row orders flips too
+        let rgba = vec![1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3];
+        assert_eq!(convert(&rgba, layout(ChannelOrder::RGBA, false, false),
+                           layout(ChannelOrder::RGBA, false, true), Size2D(1, 3)),
+                   vec![3, 3, 3, 3, 2, 2, 2, 2, 1, 1, 1, 1]);
+    }
+}
@@ -1,10 +1,34 @@
 use geom::Size2D;
+use libc::c_void;
+use Result;
+
+/// The native API used to create the GL context
+#[derive(Clone, Debug, Copy, PartialEq)]
+pub enum BackendKind {
+    GLX,
+    EGL,
+    WGL,
+    CGL,
+    OSMesa,
+    /// No backend is implemented for this platform
+    None,
+}
 
 pub trait NativeGLContextMethods {
     // TODO(ecoal95): create_headless should not require a size
-    fn create_headless(Size2D<i32>) -> Result<Self, &'static str>;
+    fn create_headless(Size2D<i32>) -> Result<Self>;
+    // Like `create_headless`, but requesting a context without error
+    // checking (GL_KHR_no_error) where the backend supports it. The
+    // caller must check whether it got one.
+    fn create_headless_no_error(size: Size2D<i32>) -> Result<Self> {
+        Self::create_headless(size)
+    }
     fn is_current(&self) -> bool;
-    fn make_current(&self) -> Result<(), &'static str>;
+    fn make_current(&self) -> Result<()>;
+    fn backend_kind(&self) -> BackendKind;
+    // Returns the address of a GL entry point (null if not found),
+    // for extension functions the GL bindings don't load.
+    fn get_proc_address(&self, name: &str) -> *const c_void;
 }
 
 #[cfg(target_os="linux")]
@@ -1,18 +1,30 @@
 use geom::Size2D;
+use libc::c_void;
 use NativeGLContextMethods;
+use BackendKind;
+use GLContextError;
+use Result;
 
 pub struct NativeGLContext;
 
 impl NativeGLContextMethods for NativeGLContext {
-    fn create_headless(_: Size2D<i32>) -> Result<NativeGLContext, &'static str> {
-        Err("Not implemented (yet)")
+    fn create_headless(_: Size2D<i32>) -> Result<NativeGLContext> {
+        Err(GLContextError::NativeContext("Not implemented (yet)"))
+    }
+
+    fn backend_kind(&self) -> BackendKind {
+        BackendKind::None
+    }
+
+    fn get_proc_address(&self, _: &str) -> *const c_void {
+        0 as *const c_void
     }
 
     fn is_current(&self) -> bool {
         false
     }
 
-    fn make_current(&self) -> Result<(), &'static str> {
-        Err("Not implemented (yet)")
+    fn make_current(&self) -> Result<()> {
+        Err(GLContextError::NativeContext("Not implemented (yet)"))
     }
 }
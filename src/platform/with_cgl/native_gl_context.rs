@@ -1,4 +1,9 @@
+use libc::{c_void, dlsym, RTLD_DEFAULT};
+use std::ffi::CString;
 use NativeGLContextMethods;
+use BackendKind;
+use GLContextError;
+use Result;
 
 pub struct NativeGLContext {
     native_context: CGLContextObj,
@@ -11,7 +16,7 @@ impl NativeGLContext {
     //   While this can be desirable, we can't rely on it.
     pub fn new(share_context: Option<NativeGLContext>,
                pixel_format: &CGLPixelFormatObj)
-        -> Result<NativeGLContext, &'static str> {
+        -> Result<NativeGLContext> {
 
         let shared = match share_context {
             Some(ctx) => ctx.as_native_cgl_context(),
@@ -22,7 +27,7 @@ impl NativeGLContext {
 
         unsafe {
             if CGLCreateContext(pixel_format, shared, &mut native) != 0 {
-                return Err("CGLCreateContext");
+                return Err(GLContextError::NativeContext("CGLCreateContext"));
             }
         }
 
@@ -39,7 +44,7 @@ impl NativeGLContext {
 }
 
 impl NativeGLContextMethods for NativeGLContext{
-    fn create_headless(size: Size2D<i32>) -> Result<NativeGLContext, &'static str> {
+    fn create_headless(size: Size2D<i32>) -> Result<NativeGLContext> {
         // NOTE: This attributes force hw acceleration,
         //   we may want to allow non hw-accelerated contexts
         let mut attributes = [
@@ -54,11 +59,11 @@ impl NativeGLContextMethods for NativeGLContext{
 
         unsafe {
             if CGLChoosePixelFormat(attributes.as_mut_ptr(), &mut pixel_format, &mut pix_count) != 0 {
-                return Err("CGLChoosePixelFormat");
+                return Err(GLContextError::NativeContext("CGLChoosePixelFormat"));
             }
 
             if pix_count == 0 {
-                return Err("No pixel formats available");
+                return Err(GLContextError::NativeContext("No pixel formats available"));
             }
         }
 
@@ -69,17 +74,34 @@ impl NativeGLContextMethods for NativeGLContext{
         result
     }
 
+    fn backend_kind(&self) -> BackendKind {
+        BackendKind::CGL
+    }
+
+    // NOTE: CGL has no GetProcAddress, every entry point is exported
+    // by the OpenGL framework
+    fn get_proc_address(&self, name: &str) -> *const c_void {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return 0 as *const c_void,
+        };
+
+        unsafe {
+            dlsym(RTLD_DEFAULT, name.as_ptr()) as *const c_void
+        }
+    }
+
     fn is_current(&self) -> bool {
         unsafe {
             CGLGetCurrentContext() == self.native_context
         }
     }
 
-    fn make_current(&self) -> Result<(), &'static str> {
+    fn make_current(&self) -> Result<()> {
         unsafe {
             if !self.is_current() &&
                 CGLSetCurrentContext(self.native_context) != 0 {
-                    Err("CGLSetCurrentContext")
+                    Err(GLContextError::NativeContext("CGLSetCurrentContext"))
             } else {
                 Ok(())
             }
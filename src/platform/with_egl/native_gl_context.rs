@@ -1,5 +1,10 @@
 use geom::Size2D;
+use libc::c_void;
+use std::ffi::CString;
 use NativeGLContextMethods;
+use BackendKind;
+use GLContextError;
+use Result;
 use platform::with_egl::utils::{create_pixel_buffer_backed_offscreen_context};
 
 
@@ -15,7 +20,7 @@ impl NativeGLContext {
            is_offscreen: bool,
            surface: EGLSurface,
            config: EGLConfig)
-        -> Result<NativeGLContext, &'static str> {
+        -> Result<NativeGLContext> {
         let shared = match share_context {
             Some(ctx) => ctx.as_native_egl_context(),
             None => egl::NO_CONTEXT
@@ -32,7 +37,7 @@ impl NativeGLContext {
 
             if native == 0 {
                 egl::DestroySurface(surface);
-                return Err("Error creating native EGL Context");
+                return Err(GLContextError::NativeContext("Error creating native EGL Context"));
             }
 
             Ok(NativeGLContext {
@@ -52,11 +57,11 @@ impl NativeGLContext {
 
 
 impl NativeGLContextMethods for NativeGLContext {
-    fn create_headless(size: Size2D<i32>) -> Result<NativeGLContext, &'static str> {
+    fn create_headless(size: Size2D<i32>) -> Result<NativeGLContext> {
         create_pixel_buffer_backed_offscreen_context(size)
     }
 
-    fn create_offscreen(size: Size2D<i32>) -> Result<NativeGLContext, &'static str> {
+    fn create_offscreen(size: Size2D<i32>) -> Result<NativeGLContext> {
         let context = try!(create_headless(size));
 
         try!(context.init_offscreen(size));
@@ -64,14 +69,36 @@ impl NativeGLContextMethods for NativeGLContext {
         Ok(())
     }
 
-    fn make_current(&self) -> Result<(), &'static str> {
+    #[inline(always)]
+    fn backend_kind(&self) -> BackendKind {
+        BackendKind::EGL
+    }
+
+    fn get_proc_address(&self, name: &str) -> *const c_void {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return 0 as *const c_void,
+        };
+
+        unsafe {
+            egl::GetProcAddress(name.as_ptr()) as *const c_void
+        }
+    }
+
+    fn is_current(&self) -> bool {
+        unsafe {
+            egl::GetCurrentContext() == self.native_context
+        }
+    }
+
+    fn make_current(&self) -> Result<()> {
         unsafe {
-            if egl::GetCurrentContext() != self.native_context &&
+            if !self.is_current() &&
                 egl::MakeCurrent(self.native_display,
                                  self.native_surface,
                                  self.native_surface,
                                  self.native_context) == egl::FALSE {
-                Err("egl::MakeCurrent")
+                Err(GLContextError::NativeContext("egl::MakeCurrent"))
             } else {
                 Ok(())
             }
@@ -1,8 +1,10 @@
 use egl;
 use std::mem;
 use geom::Size2D;
+use GLContextError;
+use Result;
 
-fn create_pbuffer_surface(config: EGLConfig, size: Size2D<i32>) -> Result<EGLSurface, &'static str> {
+fn create_pbuffer_surface(config: EGLConfig, size: Size2D<i32>) -> Result<EGLSurface> {
     let mut attrs = [
         egl::WIDTH, size.width as EGLInt,
         egl::HEIGHT, size.heigh as EGLInt,
@@ -12,13 +14,13 @@ fn create_pbuffer_surface(config: EGLConfig, size: Size2D<i32>) -> Result<EGLSur
     let surface = unsafe { egl::CreatePBufferSurface(egl::Display(), config, attrs.as_mut_ptr()) };
 
     if surface == 0 {
-        return Err("egl::CreatePBufferSurface");
+        return Err(GLContextError::NativeContext("egl::CreatePBufferSurface"));
     }
 
     Ok(surface)
 }
 
-fn create_pixel_buffer_backed_offscreen_context(size: Size2D<i32>) -> Result<NativeGLContext, &'static str> {
+fn create_pixel_buffer_backed_offscreen_context(size: Size2D<i32>) -> Result<NativeGLContext> {
     let mut attributes = [
         egl::SURFACE_TYPE, egl::PBUFFER_BIT,
         egl::RENDERABLE_TYPE, egl::OPENGL_ES2_BIT,
@@ -33,11 +35,11 @@ fn create_pixel_buffer_backed_offscreen_context(size: Size2D<i32>) -> Result<Nat
     let mut found_configs : EGLint = 0;
 
     if egl::ChooseConfig(egl::Display(), attributes.as_mut_ptr(), &mut config, 1, &mut found_configs) == 0 {
-        return Err("egl::ChooseConfig");
+        return Err(GLContextError::NativeContext("egl::ChooseConfig"));
     }
 
     if found_configs == 0 {
-        return Err("No EGL config for pBuffer");
+        return Err(GLContextError::NativeContext("No EGL config for pBuffer"));
     }
 
     let surface = try!(create_pbuffer_surface(config, size));
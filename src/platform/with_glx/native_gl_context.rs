@@ -3,9 +3,73 @@ use xlib::*;
 use libc::*;
 use glx::types::{GLXContext, GLXDrawable, GLXFBConfig, GLXPixmap};
 use geom::{Size2D};
+use std::cell::Cell;
+use std::ffi::{CStr, CString};
+use std::mem;
 use super::utils::{create_offscreen_pixmap_backed_context};
 
-use platform::NativeGLContextMethods;
+use platform::{NativeGLContextMethods, BackendKind};
+use GLContextError;
+use Result;
+
+// NOTE: Not exposed by the bindings
+const CONTEXT_OPENGL_NO_ERROR_ARB: c_int = 0x31B3;
+
+type CreateContextAttribsFn = extern "C" fn(*mut glx::types::Display, GLXFBConfig, GLXContext,
+                                            glx::types::Bool, *const c_int) -> GLXContext;
+
+// Whether an X error was caught by `record_x_error` in this thread
+thread_local!(static X_ERROR_CAUGHT: Cell<bool> = Cell::new(false));
+
+// The default handler exits the process, but glXCreateContextAttribsARB
+// reports unsupported attribute combinations as X errors (BadMatch,
+// BadValue or GLXBadFBConfig), so we just record them.
+extern "C" fn record_x_error(_display: *mut Display, _event: *mut XErrorEvent) -> c_int {
+    X_ERROR_CAUGHT.with(|caught| caught.set(true));
+    0
+}
+
+// Creates a context with GLX_ARB_create_context_no_error, or returns
+// null if it's not supported.
+unsafe fn create_no_error_context(display: *mut glx::types::Display,
+                                  framebuffer_config: GLXFBConfig,
+                                  shared: GLXContext) -> GLXContext {
+    let extensions = glx::QueryExtensionsString(display, XDefaultScreen(display as *mut _));
+    if extensions.is_null() {
+        return 0 as GLXContext;
+    }
+
+    let extensions = CStr::from_ptr(extensions as *const _).to_bytes();
+    let supported = extensions.split(|&c| c == b' ')
+                              .any(|name| name == b"GLX_ARB_create_context_no_error");
+
+    let create = glx::GetProcAddress(b"glXCreateContextAttribsARB\0".as_ptr() as *const _);
+    if !supported || (create as *const c_void).is_null() {
+        return 0 as GLXContext;
+    }
+
+    let create: CreateContextAttribsFn = mem::transmute(create);
+    let attributes = [CONTEXT_OPENGL_NO_ERROR_ARB, 1, 0];
+
+    // Errors are reported asynchronously, so sync before and after the
+    // call to only catch ours
+    XSync(display as *mut _, 0);
+    X_ERROR_CAUGHT.with(|caught| caught.set(false));
+    let old_handler = XSetErrorHandler(Some(record_x_error));
+
+    let context = create(display, framebuffer_config, shared, 1, attributes.as_ptr());
+
+    XSync(display as *mut _, 0);
+    XSetErrorHandler(old_handler);
+
+    // NOTE: If there was an error the context (if any) is unusable, and
+    // the caller falls back to a regular one
+    if X_ERROR_CAUGHT.with(|caught| caught.get()) {
+        return 0 as GLXContext;
+    }
+
+    context
+}
 
 pub struct NativeGLContext {
     native_context: GLXContext,
@@ -17,15 +81,24 @@ impl NativeGLContext {
     pub fn new(share_context: Option<&NativeGLContext>,
                display: *mut glx::types::Display,
                drawable: GLXDrawable,
-               framebuffer_config: GLXFBConfig)
-        -> Result<NativeGLContext, &'static str> {
+               framebuffer_config: GLXFBConfig,
+               no_error: bool)
+        -> Result<NativeGLContext> {
 
         let shared = match share_context {
             Some(ctx) => ctx.as_native_glx_context(),
             None      => 0 as GLXContext
         };
 
-        let native = unsafe { glx::CreateNewContext(display, framebuffer_config, glx::RGBA_TYPE as c_int, shared, 1 as glx::types::Bool) };
+        let mut native = 0 as GLXContext;
+
+        if no_error {
+            native = unsafe { create_no_error_context(display, framebuffer_config, shared) };
+        }
+
+        if (native as *const c_void) == (0 as *const c_void) {
+            native = unsafe { glx::CreateNewContext(display, framebuffer_config, glx::RGBA_TYPE as c_int, shared, 1 as glx::types::Bool) };
+        }
 
         // FIXME: This should be:
         // if native == (0 as *const c_void) {
@@ -36,7 +109,7 @@ impl NativeGLContext {
         //     found a different enum `libc::types::common::c95::c_void`) [E0308]
         if (native as *const c_void) == (0 as *const c_void) {
             unsafe { glx::DestroyPixmap(display, drawable as GLXPixmap) };
-            return Err("Error creating native glx context");
+            return Err(GLContextError::NativeContext("Error creating native glx context"));
         }
 
         Ok(NativeGLContext {
@@ -65,24 +138,43 @@ impl NativeGLContextMethods for NativeGLContext {
     // FIXME(ecoal95): In Gecko this is created with a dummy size
     //   and later resized with corresponding checks about max fb length
     //   we're not handling resizing yet though
-    fn create_headless(size: Size2D<i32>) -> Result<NativeGLContext, &'static str> {
-        create_offscreen_pixmap_backed_context(size)
+    fn create_headless(size: Size2D<i32>) -> Result<NativeGLContext> {
+        create_offscreen_pixmap_backed_context(size, false)
+    }
+
+    fn create_headless_no_error(size: Size2D<i32>) -> Result<NativeGLContext> {
+        create_offscreen_pixmap_backed_context(size, true)
     }
 
     #[inline(always)]
+    fn backend_kind(&self) -> BackendKind {
+        BackendKind::GLX
+    }
+
+    fn get_proc_address(&self, name: &str) -> *const c_void {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return 0 as *const c_void,
+        };
+
+        unsafe {
+            glx::GetProcAddress(name.as_ptr() as *const _) as *const c_void
+        }
+    }
+
     fn is_current(&self) -> bool {
         unsafe {
             glx::GetCurrentContext() == self.native_context
         }
     }
 
-    fn make_current(&self) -> Result<(), &'static str> {
+    fn make_current(&self) -> Result<()> {
         unsafe {
             if !self.is_current()
                 && glx::MakeCurrent(self.native_display,
                                     self.native_drawable,
                                     self.native_context) == 0 {
-                Err("glx::MakeContextCurrent")
+                Err(GLContextError::NativeContext("glx::MakeContextCurrent"))
             } else {
                 Ok(())
             }
@@ -5,6 +5,8 @@ use libc::*;
 use geom::{Size2D};
 
 use NativeGLContext;
+use GLContextError;
+use Result;
 
 struct ScopedXFree<T> {
     ptr: *mut T
@@ -32,7 +34,7 @@ impl<T> Drop for ScopedXFree<T> {
     }
 }
 
-unsafe fn get_visual_and_depth(s: *mut Screen, id: VisualID) -> Result<(*mut Visual, c_int), &'static str> {
+unsafe fn get_visual_and_depth(s: *mut Screen, id: VisualID) -> Result<(*mut Visual, c_int)> {
     for d in 0..((*s).ndepths as isize) {
         let depth_info : *mut Depth = (*s).depths.offset(d);
         for v in 0..((*depth_info).nvisuals as isize) {
@@ -43,12 +45,12 @@ unsafe fn get_visual_and_depth(s: *mut Screen, id: VisualID) -> Result<(*mut Vis
         }
     }
 
-    Err("Visual not on screen")
+    Err(GLContextError::NativeContext("Visual not on screen"))
 }
 
 // Almost directly ported from
 // https://dxr.mozilla.org/mozilla-central/source/gfx/gl/GLContextProviderGLX.cpp
-pub fn create_offscreen_pixmap_backed_context(size: Size2D<i32>) -> Result<NativeGLContext, &'static str> {
+pub fn create_offscreen_pixmap_backed_context(size: Size2D<i32>, no_error: bool) -> Result<NativeGLContext> {
     let dpy = unsafe { XOpenDisplay(0 as *mut c_char) };
 
     // We try to get possible framebuffer configurations which
@@ -70,7 +72,7 @@ pub fn create_offscreen_pixmap_backed_context(size: Size2D<i32>) -> Result<Nativ
     });
 
     if configs.as_ptr().is_null() {
-        return Err("glx::ChooseFBConfig");
+        return Err(GLContextError::NativeContext("glx::ChooseFBConfig"));
     }
 
     debug_assert!(config_count > 0);
@@ -106,7 +108,7 @@ pub fn create_offscreen_pixmap_backed_context(size: Size2D<i32>) -> Result<Nativ
     }
 
     if visual_id == 0 {
-        return Err("We don't have any config with visuals");
+        return Err(GLContextError::NativeContext("We don't have any config with visuals"));
     }
 
     unsafe {
@@ -121,7 +123,7 @@ pub fn create_offscreen_pixmap_backed_context(size: Size2D<i32>) -> Result<Nativ
                                    depth as c_uint);
 
         if pixmap == 0 {
-            return Err("XCreatePixMap");
+            return Err(GLContextError::NativeContext("XCreatePixMap"));
         }
 
         let glx_pixmap = glx::CreatePixmap(dpy as *mut glx::types::Display,
@@ -130,11 +132,11 @@ pub fn create_offscreen_pixmap_backed_context(size: Size2D<i32>) -> Result<Nativ
                                            0 as *const c_int);
 
         if glx_pixmap == 0 {
-            return Err("glx::createPixmap");
+            return Err(GLContextError::NativeContext("glx::createPixmap"));
         }
 
         let chosen_config = *configs.as_ptr().offset(config_index);
 
-        NativeGLContext::new(None, dpy as *mut glx::types::Display, glx_pixmap as GLXDrawable, chosen_config)
+        NativeGLContext::new(None, dpy as *mut glx::types::Display, glx_pixmap as GLXDrawable, chosen_config, no_error)
     }
 }
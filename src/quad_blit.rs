@@ -0,0 +1,465 @@
+use geom::Size2D;
+use gleam::gl;
+use gleam::gl::types::{GLuint, GLenum, GLint, GLboolean, GLfloat, GLsizeiptr, GLvoid};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::ptr;
+
+use GLStateGuard;
+use GLContextError;
+use Result;
+use gl_context::current_context_id;
+use gl_errors::check_gl_error;
+use gl_feature::get_gl_version;
+
+// Emulates glBlitFramebuffer (which GLES2 lacks) by drawing a textured
+// quad. It's also used to composite with blending, which blits ignore.
+
+// GLSL ES 1.00, for GLES (any version accepts it)
+static VERTEX_SHADER_ES: &'static str = "#version 100
+attribute vec2 a_position;
+varying vec2 v_texcoord;
+void main() {
+    v_texcoord = a_position;
+    gl_Position = vec4(a_position * 2.0 - 1.0, 0.0, 1.0);
+}
+";
+
+static FRAGMENT_SHADER_ES: &'static str = "#version 100
+precision mediump float;
+uniform sampler2D u_texture;
+varying vec2 v_texcoord;
+void main() {
+    gl_FragColor = texture2D(u_texture, v_texcoord);
+}
+";
+
+// GLSL 1.10, for desktop GL older than 3.2
+static VERTEX_SHADER_LEGACY: &'static str = "#version 110
+attribute vec2 a_position;
+varying vec2 v_texcoord;
+void main() {
+    v_texcoord = a_position;
+    gl_Position = vec4(a_position * 2.0 - 1.0, 0.0, 1.0);
+}
+";
+
+static FRAGMENT_SHADER_LEGACY: &'static str = "#version 110
+uniform sampler2D u_texture;
+varying vec2 v_texcoord;
+void main() {
+    gl_FragColor = texture2D(u_texture, v_texcoord);
+}
+";
+
+// GLSL 1.50, for desktop GL 3.2 and later, since core profiles
+// reject attribute/varying, gl_FragColor and texture2D
+static VERTEX_SHADER_CORE: &'static str = "#version 150
+in vec2 a_position;
+out vec2 v_texcoord;
+void main() {
+    v_texcoord = a_position;
+    gl_Position = vec4(a_position * 2.0 - 1.0, 0.0, 1.0);
+}
+";
+
+static FRAGMENT_SHADER_CORE: &'static str = "#version 150
+uniform sampler2D u_texture;
+in vec2 v_texcoord;
+out vec4 o_color;
+void main() {
+    o_color = texture(u_texture, v_texcoord);
+}
+";
+
+// Returns the vertex and fragment shader sources for the current context
+fn shader_sources() -> (&'static str, &'static str) {
+    match get_gl_version() {
+        (true, _) => (VERTEX_SHADER_ES, FRAGMENT_SHADER_ES),
+        (false, version) if version >= 32 => (VERTEX_SHADER_CORE, FRAGMENT_SHADER_CORE),
+        (false, _) => (VERTEX_SHADER_LEGACY, FRAGMENT_SHADER_LEGACY),
+    }
+}
+
+// Core profiles can't draw without a vertex array object bound, so
+// on desktop GL 3.0 and later the blitter uses its own. That also
+// leaves the application's vertex array untouched.
+fn uses_vertex_arrays() -> bool {
+    let (is_gles, version) = get_gl_version();
+    !is_gles && version >= 30
+}
+
+static QUAD_VERTICES: [GLfloat; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+
+const POSITION_LOCATION: GLuint = 0;
+
+const CURRENT_PROGRAM: GLenum = 0x8B8D;
+const ARRAY_BUFFER_BINDING: GLenum = 0x8894;
+const ACTIVE_TEXTURE: GLenum = 0x84E0;
+const BLEND_SRC_RGB: GLenum = 0x80C9;
+const BLEND_DST_RGB: GLenum = 0x80C8;
+const BLEND_SRC_ALPHA: GLenum = 0x80CB;
+const BLEND_DST_ALPHA: GLenum = 0x80CA;
+const VERTEX_ATTRIB_ARRAY_ENABLED: GLenum = 0x8622;
+const VERTEX_ATTRIB_ARRAY_SIZE: GLenum = 0x8623;
+const VERTEX_ATTRIB_ARRAY_STRIDE: GLenum = 0x8624;
+const VERTEX_ATTRIB_ARRAY_TYPE: GLenum = 0x8625;
+const VERTEX_ATTRIB_ARRAY_NORMALIZED: GLenum = 0x886A;
+const VERTEX_ATTRIB_ARRAY_BUFFER_BINDING: GLenum = 0x889F;
+const VERTEX_ATTRIB_ARRAY_POINTER: GLenum = 0x8645;
+const VERTEX_ARRAY_BINDING: GLenum = 0x85B5;
+
+struct QuadBlitter {
+    program: GLuint,
+    texture_location: GLint,
+    vertex_buffer: GLuint,
+    // Only if `uses_vertex_arrays`
+    vertex_array: Option<GLuint>,
+}
+
+// One blitter per context, compiled on first use. They're keyed by the
+// id of the context, so they must be used with a context we know is current.
+thread_local!(static BLITTERS: RefCell<HashMap<usize, QuadBlitter>> = RefCell::new(HashMap::new()));
+
+fn compile_shader(kind: GLenum, source: &'static str) -> Result<GLuint> {
+    unsafe {
+        let shader = gl::CreateShader(kind);
+        let source_ptr = source.as_ptr() as *const _;
+        let length = source.len() as GLint;
+        gl::ShaderSource(shader, 1, &source_ptr, &length);
+        gl::CompileShader(shader);
+
+        let mut status: GLint = 0;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+
+        if status == 0 {
+            gl::DeleteShader(shader);
+            return Err(GLContextError::GLError("Couldn't compile the blit shader"));
+        }
+
+        Ok(shader)
+    }
+}
+
+impl QuadBlitter {
+    fn new() -> Result<QuadBlitter> {
+        let (vertex_source, fragment_source) = shader_sources();
+        let vertex_shader = try!(compile_shader(gl::VERTEX_SHADER, vertex_source));
+        let fragment_shader = match compile_shader(gl::FRAGMENT_SHADER, fragment_source) {
+            Ok(shader) => shader,
+            Err(err) => {
+                unsafe { gl::DeleteShader(vertex_shader) };
+                return Err(err);
+            }
+        };
+
+        unsafe {
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vertex_shader);
+            gl::AttachShader(program, fragment_shader);
+            gl::BindAttribLocation(program, POSITION_LOCATION, b"a_position\0".as_ptr() as *const _);
+            gl::LinkProgram(program);
+
+            // The program keeps them alive
+            gl::DeleteShader(vertex_shader);
+            gl::DeleteShader(fragment_shader);
+
+            let mut status: GLint = 0;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+
+            if status == 0 {
+                gl::DeleteProgram(program);
+                return Err(GLContextError::GLError("Couldn't link the blit program"));
+            }
+
+            let texture_location = gl::GetUniformLocation(program, b"u_texture\0".as_ptr() as *const _);
+
+            let mut vertex_buffer: GLuint = 0;
+            let mut previous_buffer: GLint = 0;
+            gl::GetIntegerv(ARRAY_BUFFER_BINDING, &mut previous_buffer);
+            gl::GenBuffers(1, &mut vertex_buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+            gl::BufferData(gl::ARRAY_BUFFER,
+                           mem::size_of_val(&QUAD_VERTICES) as GLsizeiptr,
+                           QUAD_VERTICES.as_ptr() as *const _,
+                           gl::STATIC_DRAW);
+            gl::BindBuffer(gl::ARRAY_BUFFER, previous_buffer as GLuint);
+
+            let vertex_array = if uses_vertex_arrays() {
+                let mut vertex_array: GLuint = 0;
+                gl::GenVertexArrays(1, &mut vertex_array);
+                Some(vertex_array)
+            } else {
+                None
+            };
+
+            Ok(QuadBlitter {
+                program: program,
+                texture_location: texture_location,
+                vertex_buffer: vertex_buffer,
+                vertex_array: vertex_array,
+            })
+        }
+    }
+
+    fn delete(&self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+            gl::DeleteBuffers(1, &self.vertex_buffer);
+            if let Some(vertex_array) = self.vertex_array {
+                gl::DeleteVertexArrays(1, &vertex_array);
+            }
+        }
+    }
+
+    // NOTE: Expects the source texture bound to unit 0, and the
+    // destination framebuffer bound.
+    fn draw(&self) {
+        unsafe {
+            if let Some(vertex_array) = self.vertex_array {
+                gl::BindVertexArray(vertex_array);
+            }
+            gl::UseProgram(self.program);
+            gl::Uniform1i(self.texture_location, 0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer);
+            gl::EnableVertexAttribArray(POSITION_LOCATION);
+            gl::VertexAttribPointer(POSITION_LOCATION, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+        }
+    }
+}
+
+fn get_integer(name: GLenum) -> GLint {
+    let mut value: GLint = 0;
+    unsafe {
+        gl::GetIntegerv(name, &mut value);
+    }
+    value
+}
+
+fn get_attrib(name: GLenum) -> GLint {
+    let mut value: GLint = 0;
+    unsafe {
+        gl::GetVertexAttribiv(POSITION_LOCATION, name, &mut value);
+    }
+    value
+}
+
+// The state of the vertex attribute we use, in the bound vertex array
+struct AttribState {
+    enabled: GLint,
+    size: GLint,
+    type_: GLint,
+    normalized: GLint,
+    stride: GLint,
+    buffer: GLint,
+    pointer: *mut GLvoid,
+}
+
+impl AttribState {
+    fn new() -> AttribState {
+        let mut pointer: *mut GLvoid = ptr::null_mut();
+        unsafe {
+            gl::GetVertexAttribPointerv(POSITION_LOCATION, VERTEX_ATTRIB_ARRAY_POINTER, &mut pointer);
+        }
+
+        AttribState {
+            enabled: get_attrib(VERTEX_ATTRIB_ARRAY_ENABLED),
+            size: get_attrib(VERTEX_ATTRIB_ARRAY_SIZE),
+            type_: get_attrib(VERTEX_ATTRIB_ARRAY_TYPE),
+            normalized: get_attrib(VERTEX_ATTRIB_ARRAY_NORMALIZED),
+            stride: get_attrib(VERTEX_ATTRIB_ARRAY_STRIDE),
+            buffer: get_attrib(VERTEX_ATTRIB_ARRAY_BUFFER_BINDING),
+            pointer: pointer,
+        }
+    }
+
+    fn restore(&self) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.buffer as GLuint);
+            gl::VertexAttribPointer(POSITION_LOCATION, self.size, self.type_ as GLenum,
+                                    self.normalized as GLboolean, self.stride,
+                                    self.pointer as *const _);
+            if self.enabled == 0 {
+                gl::DisableVertexAttribArray(POSITION_LOCATION);
+            }
+        }
+    }
+}
+
+// Snapshots (and restores on drop) the drawing state the quad blit
+// touches, on top of what `GLStateGuard` saves.
+struct DrawStateGuard {
+    program: GLuint,
+    array_buffer: GLuint,
+    active_texture: GLenum,
+    texture: GLuint,
+    viewport: [GLint; 4],
+    capabilities: [(GLenum, GLboolean); 5],
+    blend_func: [GLenum; 4],
+    // The bound vertex array, if the blitter uses its own
+    vertex_array: Option<GLuint>,
+    // Otherwise, the attribute we modify in the application's one
+    attrib: Option<AttribState>,
+}
+
+impl DrawStateGuard {
+    fn new() -> DrawStateGuard {
+        let mut viewport: [GLint; 4] = [0; 4];
+        let mut capabilities = [(gl::BLEND, 0), (gl::SCISSOR_TEST, 0), (gl::DEPTH_TEST, 0),
+                                (gl::STENCIL_TEST, 0), (gl::CULL_FACE, 0)];
+
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+
+            for capability in capabilities.iter_mut() {
+                capability.1 = gl::IsEnabled(capability.0);
+            }
+        }
+
+        let (vertex_array, attrib) = if uses_vertex_arrays() {
+            (Some(get_integer(VERTEX_ARRAY_BINDING) as GLuint), None)
+        } else {
+            (None, Some(AttribState::new()))
+        };
+
+        let active_texture = get_integer(ACTIVE_TEXTURE) as GLenum;
+
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+        }
+
+        DrawStateGuard {
+            program: get_integer(CURRENT_PROGRAM) as GLuint,
+            array_buffer: get_integer(ARRAY_BUFFER_BINDING) as GLuint,
+            active_texture: active_texture,
+            texture: get_integer(gl::TEXTURE_BINDING_2D) as GLuint,
+            viewport: viewport,
+            capabilities: capabilities,
+            blend_func: [get_integer(BLEND_SRC_RGB) as GLenum, get_integer(BLEND_DST_RGB) as GLenum,
+                         get_integer(BLEND_SRC_ALPHA) as GLenum, get_integer(BLEND_DST_ALPHA) as GLenum],
+            vertex_array: vertex_array,
+            attrib: attrib,
+        }
+    }
+}
+
+impl Drop for DrawStateGuard {
+    fn drop(&mut self) {
+        if let Some(vertex_array) = self.vertex_array {
+            unsafe {
+                gl::BindVertexArray(vertex_array);
+            }
+        }
+
+        if let Some(ref attrib) = self.attrib {
+            attrib.restore();
+        }
+
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.array_buffer);
+            gl::UseProgram(self.program);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::ActiveTexture(self.active_texture);
+            gl::Viewport(self.viewport[0], self.viewport[1], self.viewport[2], self.viewport[3]);
+
+            for &(capability, enabled) in self.capabilities.iter() {
+                if enabled == gl::TRUE {
+                    gl::Enable(capability);
+                } else {
+                    gl::Disable(capability);
+                }
+            }
+
+            gl::BlendFuncSeparate(self.blend_func[0], self.blend_func[1],
+                                  self.blend_func[2], self.blend_func[3]);
+        }
+    }
+}
+
+/// Copies the color buffer of the (single-sampled) `source` framebuffer
+/// into `dest`, by copying it to a temporary texture and drawing it as a
+/// quad with `filter`. If `blend` is true, it's composited with
+/// premultiplied source-over blending.
+///
+/// NOTE: Requires the context to be current.
+pub fn blit(source: GLuint, source_size: &Size2D<i32>,
+            dest: GLuint, dest_size: &Size2D<i32>,
+            filter: GLenum, blend: bool) -> Result<()> {
+    let _guard = GLStateGuard::new();
+    let _draw_guard = DrawStateGuard::new();
+
+    let mut texture: GLuint = 0;
+
+    unsafe {
+        // NOTE: GLES2 can only copy into a texture, not sample the
+        // framebuffer directly.
+        gl::BindFramebuffer(gl::FRAMEBUFFER, source);
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::CopyTexImage2D(gl::TEXTURE_2D, 0, gl::RGBA, 0, 0,
+                           source_size.width, source_size.height, 0);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as GLint);
+        // NOTE: Required for non-power-of-two textures on GLES2
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, dest);
+        gl::Viewport(0, 0, dest_size.width, dest_size.height);
+        gl::Disable(gl::SCISSOR_TEST);
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::STENCIL_TEST);
+        gl::Disable(gl::CULL_FACE);
+
+        if blend {
+            gl::Enable(gl::BLEND);
+            gl::BlendFuncSeparate(gl::ONE, gl::ONE_MINUS_SRC_ALPHA,
+                                  gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        } else {
+            gl::Disable(gl::BLEND);
+        }
+    }
+
+    let id = current_context_id();
+
+    let result = BLITTERS.with(|blitters| {
+        let mut blitters = blitters.borrow_mut();
+
+        // If we don't know which context is current we can't cache it
+        if id == 0 {
+            let blitter = try!(QuadBlitter::new());
+            blitter.draw();
+            blitter.delete();
+            return Ok(());
+        }
+
+        if !blitters.contains_key(&id) {
+            blitters.insert(id, try!(QuadBlitter::new()));
+        }
+
+        blitters[&id].draw();
+
+        Ok(())
+    });
+
+    unsafe {
+        gl::DeleteTextures(1, &texture);
+    }
+
+    try!(result);
+
+    check_gl_error("Quad blit")
+}
+
+/// Deletes the cached blitter of the context with id `id`, if any.
+///
+/// NOTE: Requires that context to be current.
+pub fn delete_blitter(id: usize) {
+    BLITTERS.with(|blitters| {
+        if let Some(blitter) = blitters.borrow_mut().remove(&id) {
+            blitter.delete();
+        }
+    });
+}
@@ -0,0 +1,151 @@
+use geom::Size2D;
+
+use pixels;
+use pixels::{AlphaMode, ChannelOrder, PixelLayout};
+
+/// The contents of a draw buffer in a caller-chosen layout, with the
+/// metadata needed to interpret them. See `DrawBuffer::capture_as`.
+#[derive(Clone, Debug)]
+pub struct CapturedFrame {
+    pub width: i32,
+    pub height: i32,
+    /// Bytes per row. Rows are always tightly packed.
+    pub stride: usize,
+    pub layout: PixelLayout,
+    /// Whether the draw buffer had no alpha channel, so alpha is 1
+    /// everywhere (or missing, with `ChannelOrder::RGB`)
+    pub opaque: bool,
+    pub pixels: Vec<u8>,
+}
+
+impl CapturedFrame {
+    #[inline(always)]
+    pub fn size(&self) -> Size2D<i32> {
+        Size2D(self.width, self.height)
+    }
+
+    /// The alpha convention of `pixels`, after any conversion done
+    /// while capturing. Opaque pixels are the same either way.
+    ///
+    /// Dropping alpha (with `ChannelOrder::RGB`) doesn't undo
+    /// premultiplication, so such pixels still report `Premultiplied`.
+    pub fn alpha_mode(&self) -> AlphaMode {
+        if self.opaque {
+            AlphaMode::Opaque
+        } else if self.layout.channels == ChannelOrder::RGB {
+            if self.layout.premultiplied { AlphaMode::Premultiplied } else { AlphaMode::Opaque }
+        } else if self.layout.premultiplied {
+            AlphaMode::Premultiplied
+        } else {
+            AlphaMode::Straight
+        }
+    }
+}
+
+/// A self-contained copy of the contents of a draw buffer, which doesn't
+/// depend on any GL context (so it can be sent to other threads).
+///
+/// See `DrawBuffer::snapshot`.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub width: i32,
+    pub height: i32,
+    pub format: PixelLayout,
+    pub pixels: Vec<u8>,
+}
+
+impl Snapshot {
+    #[inline(always)]
+    pub fn size(&self) -> Size2D<i32> {
+        Size2D(self.width, self.height)
+    }
+
+    /// Returns the pixels in `layout`, converting them if needed.
+    pub fn into_layout(self, layout: PixelLayout) -> Vec<u8> {
+        if self.format == layout {
+            return self.pixels;
+        }
+
+        pixels::convert(&self.pixels, self.format, layout, self.size())
+    }
+
+    /// Returns the pixels as RGBA, keeping the row order and
+    /// premultiplication.
+    pub fn into_rgba(self) -> Vec<u8> {
+        let layout = PixelLayout { channels: ChannelOrder::RGBA, .. self.format };
+        self.into_layout(layout)
+    }
+
+    /// Returns the pixels as BGRA, keeping the row order and
+    /// premultiplication.
+    pub fn into_bgra(self) -> Vec<u8> {
+        let layout = PixelLayout { channels: ChannelOrder::BGRA, .. self.format };
+        self.into_layout(layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pixels::{AlphaMode, ChannelOrder, PixelLayout};
+    use super::{CapturedFrame, Snapshot};
+
+    fn layout(channels: ChannelOrder, premultiplied: bool) -> PixelLayout {
+        PixelLayout {
+            channels: channels,
+            premultiplied: premultiplied,
+            top_down: false,
+        }
+    }
+
+    // A 1x2 image, bottom row first, with a half transparent pixel on top
+    fn snapshot() -> Snapshot {
+        Snapshot {
+            width: 1,
+            height: 2,
+            format: layout(ChannelOrder::RGBA, true),
+            pixels: vec![10, 20, 30, 255, 64, 32, 16, 128],
+        }
+    }
+
+    fn frame(channels: ChannelOrder, premultiplied: bool, opaque: bool) -> CapturedFrame {
+        CapturedFrame {
+            width: 1,
+            height: 1,
+            stride: channels.bytes_per_pixel(),
+            layout: layout(channels, premultiplied),
+            opaque: opaque,
+            pixels: vec![0; channels.bytes_per_pixel()],
+        }
+    }
+
+    #[test]
+    fn into_rgba_keeps_the_layout() {
+        assert_eq!(snapshot().into_rgba(), snapshot().pixels);
+    }
+
+    #[test]
+    fn into_bgra_only_swaps_red_and_blue() {
+        assert_eq!(snapshot().into_bgra(), vec![30, 20, 10, 255, 16, 32, 64, 128]);
+    }
+
+    #[test]
+    fn into_layout_converts_alpha_and_row_order() {
+        let straight = PixelLayout { top_down: true, .. layout(ChannelOrder::RGBA, false) };
+        assert_eq!(snapshot().into_layout(straight), vec![128, 64, 32, 128, 10, 20, 30, 255]);
+
+        // Dropping alpha keeps (or undoes) premultiplication as asked
+        assert_eq!(snapshot().into_layout(layout(ChannelOrder::RGB, true)),
+                   vec![10, 20, 30, 64, 32, 16]);
+        assert_eq!(snapshot().into_layout(layout(ChannelOrder::RGB, false)),
+                   vec![10, 20, 30, 128, 64, 32]);
+    }
+
+    #[test]
+    fn alpha_modes() {
+        assert_eq!(frame(ChannelOrder::RGBA, true, false).alpha_mode(), AlphaMode::Premultiplied);
+        assert_eq!(frame(ChannelOrder::BGRA, false, false).alpha_mode(), AlphaMode::Straight);
+        assert_eq!(frame(ChannelOrder::RGBA, true, true).alpha_mode(), AlphaMode::Opaque);
+        assert_eq!(frame(ChannelOrder::RGB, true, false).alpha_mode(), AlphaMode::Premultiplied);
+        assert_eq!(frame(ChannelOrder::RGB, false, false).alpha_mode(), AlphaMode::Opaque);
+    }
+}